@@ -0,0 +1,352 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime},
+};
+
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+
+use crate::{network::broadcast::Format, reader::StateIntervals, structs::Arm};
+
+/// How often config file is checked for changes
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Settings from `config.toml`, missing fields use
+/// the same defaults as command line arguments
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Interval between updates in ms
+    pub interval: u64,
+
+    /// Amount of seconds waiting after critical error happened
+    pub error_interval: u64,
+
+    pub intervals: Intervals,
+
+    /// Interval of reading precise values (key overlay, hit
+    /// errors) between updates in ms, `0` (default) disables it
+    pub precise_interval: u64,
+
+    pub osu_path: Option<PathBuf>,
+
+    pub host: IpAddr,
+    pub port: u16,
+
+    /// Additional addresses to listen on
+    pub bind: Vec<SocketAddr>,
+
+    /// Folder with counters (overlays),
+    /// `counters` folder next to the executable by default
+    pub static_dir: Option<PathBuf>,
+
+    pub endpoints: Endpoints,
+
+    /// Formats websocket clients are allowed to request,
+    /// JSON is used for everything else
+    pub formats: Vec<Format>,
+
+    pub logging: Logging,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            interval: 300,
+            error_interval: 3,
+            intervals: Intervals::default(),
+            precise_interval: 0,
+            osu_path: None,
+            host: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 24050,
+            bind: Vec::new(),
+            static_dir: None,
+            endpoints: Endpoints::default(),
+            formats: vec![Format::Json, Format::MessagePack, Format::Cbor],
+            logging: Logging::default(),
+        }
+    }
+}
+
+/// Intervals between updates in ms for some game
+/// states, `interval` is used when not set
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Intervals {
+    pub playing: Option<u64>,
+    pub song_select: Option<u64>,
+    pub idle: Option<u64>,
+}
+
+/// Endpoints that can be turned off, everything is enabled by default
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Endpoints {
+    /// `/ws`
+    pub ws: bool,
+
+    /// `/rws`
+    pub rws: bool,
+
+    /// `/events`
+    pub events: bool,
+
+    /// `/precise`
+    pub precise: bool,
+
+    /// `/sse` and `/sse/gosu`
+    pub sse: bool,
+
+    /// `/json`
+    pub json: bool,
+
+    /// `/metrics`
+    pub metrics: bool,
+
+    /// `/status`
+    pub status: bool,
+
+    /// `/Songs/`
+    pub songs: bool,
+
+    /// `/skin/` and `/skin.json`
+    pub skin: bool,
+
+    /// `/counters` and counters' files
+    pub counters: bool,
+}
+
+impl Endpoints {
+    /// Whether endpoint serving `path` is enabled
+    pub fn is_enabled(&self, path: &str) -> bool {
+        match path {
+            "/ws" => self.ws,
+            "/rws" => self.rws,
+            "/events" => self.events,
+            "/precise" => self.precise,
+            "/sse" | "/sse/gosu" => self.sse,
+            "/metrics" => self.metrics,
+            "/status" => self.status,
+            "/skin.json" => self.skin,
+            path if path == "/json" || path.starts_with("/json/") => self.json,
+            path if path.starts_with("/Songs/") => self.songs,
+            path if path.starts_with("/skin/") => self.skin,
+            _ => self.counters,
+        }
+    }
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            ws: true,
+            rws: true,
+            events: true,
+            precise: true,
+            sse: true,
+            json: true,
+            metrics: true,
+            status: true,
+            songs: true,
+            skin: true,
+            counters: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Logging {
+    /// Print errors of the reading loop
+    pub errors: bool,
+
+    /// Print game events, e.g. `play_started`
+    pub events: bool,
+}
+
+impl Default for Logging {
+    fn default() -> Self {
+        Self {
+            errors: true,
+            events: false,
+        }
+    }
+}
+
+impl Config {
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read config: {}", path.display()))?;
+
+        Self::parse(&text).wrap_err_with(|| format!("Invalid config: {}", path.display()))
+    }
+
+    pub fn interval(&self) -> Duration {
+        Duration::from_millis(self.interval)
+    }
+
+    pub fn error_interval(&self) -> Duration {
+        Duration::from_secs(self.error_interval)
+    }
+
+    pub fn state_intervals(&self) -> StateIntervals {
+        StateIntervals {
+            playing: self.intervals.playing.map(Duration::from_millis),
+            song_select: self.intervals.song_select.map(Duration::from_millis),
+            idle: self.intervals.idle.map(Duration::from_millis),
+        }
+    }
+
+    pub fn precise_interval(&self) -> Option<Duration> {
+        (self.precise_interval > 0).then(|| Duration::from_millis(self.precise_interval))
+    }
+
+    /// All addresses server should listen on
+    /// without duplicates
+    pub fn listen_addresses(&self) -> Vec<SocketAddr> {
+        let mut addresses = vec![SocketAddr::new(self.host, self.port)];
+
+        for addr in &self.bind {
+            if !addresses.contains(addr) {
+                addresses.push(*addr);
+            }
+        }
+
+        addresses
+    }
+
+    pub fn static_dir(&self) -> PathBuf {
+        if let Some(ref dir) = self.static_dir {
+            return dir.clone();
+        }
+
+        std::env::current_exe()
+            .ok()
+            .and_then(|v| v.parent().map(|v| v.join("counters")))
+            .unwrap_or_else(|| PathBuf::from("counters"))
+    }
+
+    /// Whether changing from `self` to `other`
+    /// only takes effect after restart
+    pub fn needs_restart(&self, other: &Self) -> bool {
+        self.osu_path != other.osu_path
+            || self.host != other.host
+            || self.port != other.port
+            || self.bind != other.bind
+    }
+}
+
+/// Reloads config every time file is modified, `overrides`
+/// is applied on top of every loaded config. Never returns
+pub fn watch(path: &Path, config: Arm<Config>, overrides: impl Fn(&mut Config)) {
+    let modified = |path: &Path| -> Option<SystemTime> { fs::metadata(path).ok()?.modified().ok() };
+
+    let mut last_modified = modified(path);
+
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+
+        let current = modified(path);
+        if current.is_none() || current == last_modified {
+            continue;
+        }
+
+        last_modified = current;
+
+        let mut new_config = match Config::load(path) {
+            Ok(v) => v,
+            Err(e) => {
+                println!("{:?}", e);
+                println!("Keeping previous config");
+                continue;
+            }
+        };
+
+        overrides(&mut new_config);
+
+        let mut config = config.lock().unwrap();
+
+        if config.needs_restart(&new_config) {
+            println!("osu! path and listen addresses are applied only after restart");
+
+            new_config.osu_path.clone_from(&config.osu_path);
+            new_config.host = config.host;
+            new_config.port = config.port;
+            new_config.bind.clone_from(&config.bind);
+        }
+
+        if *config == new_config {
+            continue;
+        }
+
+        println!("Reloaded config: {}", path.display());
+        *config = new_config;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let config = Config::parse(
+            r#"
+            interval = 100
+            port = 24051
+            bind = ["192.168.1.10:24050"]
+            formats = ["json", "msgpack"]
+
+            [intervals]
+            playing = 16
+
+            [endpoints]
+            metrics = false
+
+            [logging]
+            events = true
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.interval(), Duration::from_millis(100));
+        assert_eq!(config.error_interval(), Duration::from_secs(3));
+        assert_eq!(
+            config.state_intervals().playing,
+            Some(Duration::from_millis(16))
+        );
+        assert_eq!(config.state_intervals().idle, None);
+        assert_eq!(config.precise_interval(), None);
+        assert_eq!(config.listen_addresses().len(), 2);
+        assert_eq!(config.formats, vec![Format::Json, Format::MessagePack]);
+        assert!(!config.endpoints.is_enabled("/metrics"));
+        assert!(config.endpoints.is_enabled("/ws"));
+        assert!(config.endpoints.is_enabled("/json/rosu/gameplay"));
+        assert!(config.logging.errors);
+        assert!(config.logging.events);
+
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert!(Config::parse("intervall = 100").is_err());
+        assert!(Config::parse("formats = [\"xml\"]").is_err());
+    }
+
+    #[test]
+    fn test_readme_config() {
+        let readme = include_str!("../README.MD");
+        let start = readme.find("```toml").unwrap() + "```toml".len();
+        let end = start + readme[start..].find("```").unwrap();
+
+        let config = Config::parse(&readme[start..end]).unwrap();
+
+        assert_eq!(config.precise_interval(), Some(Duration::from_millis(16)));
+        assert_eq!(config.formats.len(), 3);
+        assert_eq!(config.state_intervals().idle, Some(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,89 @@
+use serde::Serialize;
+
+use crate::structs::{GameState, OutputValues};
+
+/// Game transitions detected by the reading loop
+///
+/// Serialized as `{"event": "play_started", "data": {...}}`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "snake_case")]
+pub enum GameEvent {
+    StateChanged {
+        from: GameState,
+        to: GameState,
+    },
+    BeatmapChanged {
+        map_id: i32,
+        mapset_id: i32,
+        md5: String,
+        artist: String,
+        title: String,
+        creator: String,
+        difficulty: String,
+    },
+    /// Mods changed on `SongSelect` state
+    ModsChanged {
+        from: u32,
+        to: u32,
+    },
+    PlayStarted {
+        map_id: i32,
+        mods: u32,
+        mode: i32,
+    },
+    PlayRetried {
+        map_id: i32,
+        mods: u32,
+    },
+    /// Left `Playing` state without reaching result screen.
+    /// `quit` is true when player's HP wasn't depleted
+    PlayFailed {
+        quit: bool,
+        #[serde(flatten)]
+        play: PlayResult,
+    },
+    PlayCompleted(PlayResult),
+}
+
+impl GameEvent {
+    /// Same as `event` field of the serialized event
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::StateChanged { .. } => "state_changed",
+            Self::BeatmapChanged { .. } => "beatmap_changed",
+            Self::ModsChanged { .. } => "mods_changed",
+            Self::PlayStarted { .. } => "play_started",
+            Self::PlayRetried { .. } => "play_retried",
+            Self::PlayFailed { .. } => "play_failed",
+            Self::PlayCompleted(_) => "play_completed",
+        }
+    }
+}
+
+/// Last known gameplay values before they were reset
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayResult {
+    pub map_id: i32,
+    pub mods: u32,
+    pub score: i32,
+    pub accuracy: f64,
+    pub max_combo: i16,
+    pub hit_miss: i16,
+    pub pp: f64,
+    pub playtime: i32,
+}
+
+impl From<&OutputValues> for PlayResult {
+    fn from(values: &OutputValues) -> Self {
+        Self {
+            map_id: values.beatmap.map_id,
+            mods: values.gameplay.mods,
+            score: values.gameplay.score,
+            accuracy: values.gameplay.accuracy,
+            max_combo: values.gameplay.max_combo,
+            hit_miss: values.gameplay.hit_miss,
+            pp: values.current_pp,
+            playtime: values.prev_playtime,
+        }
+    }
+}
@@ -1,6 +1,6 @@
 use serde::Serialize;
 
-use crate::structs::{BeatmapStatus, GameState, OutputValues};
+use crate::structs::{BeatmapStatus, GameState, LeaderboardEntry, OutputValues, readable_mods};
 
 #[derive(Debug, Serialize)]
 pub struct GosuMenu {
@@ -157,6 +157,54 @@ pub struct GosuGameplayHits {
     // TODO hitErrorArray
 }
 
+#[derive(Debug, Serialize)]
+pub struct GosuLeaderboardPlayer {
+    name: String,
+    score: i32,
+    combo: i16,
+    #[serde(rename = "maxCombo")]
+    max_combo: i16,
+    mods: String,
+    h300: i16,
+    h100: i16,
+    h50: i16,
+    h0: i16,
+    team: i32,
+    position: i32,
+    #[serde(rename = "isPassing")]
+    is_passing: u8,
+}
+
+impl From<&LeaderboardEntry> for GosuLeaderboardPlayer {
+    fn from(value: &LeaderboardEntry) -> Self {
+        GosuLeaderboardPlayer {
+            name: value.name.clone(),
+            score: value.score,
+            combo: value.combo,
+            max_combo: value.max_combo,
+            mods: readable_mods(value.mods).concat(),
+            h300: value.hit_300,
+            h100: value.hit_100,
+            h50: value.hit_50,
+            h0: value.hit_miss,
+            team: value.team,
+            position: value.position,
+            is_passing: value.is_passing as u8,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct GosuLeaderboard {
+    #[serde(rename = "hasLeaderboard")]
+    has_leaderboard: bool,
+
+    #[serde(rename = "ourplayer")]
+    our_player: Option<GosuLeaderboardPlayer>,
+
+    slots: Vec<GosuLeaderboardPlayer>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct GosuGameplay {
     #[serde(rename = "gameMode")]
@@ -170,12 +218,38 @@ pub struct GosuGameplay {
     hits: GosuGameplayHits,
 
     pp: GosuGameplayPp,
+
+    leaderboard: GosuLeaderboard,
+}
+
+#[derive(Debug, Serialize)]
+pub struct GosuUserProfile {
+    name: String,
+    id: i32,
+    accuracy: f64,
+    #[serde(rename = "rankedScore")]
+    ranked_score: i64,
+    level: f32,
+    #[serde(rename = "playCount")]
+    play_count: i32,
+    #[serde(rename = "playMode")]
+    play_mode: i32,
+    rank: i32,
+    #[serde(rename = "performancePoints")]
+    pp: i16,
+    #[serde(rename = "rawBanchoStatus")]
+    bancho_status: u8,
+    #[serde(rename = "countryCode")]
+    country_code: i32,
 }
 
 #[derive(Debug, Serialize)]
 pub struct GosuValues {
     menu: GosuMenu,
     gameplay: GosuGameplay,
+
+    #[serde(rename = "userProfile")]
+    user_profile: GosuUserProfile,
 }
 
 impl From<&OutputValues> for GosuValues {
@@ -273,6 +347,32 @@ impl From<&OutputValues> for GosuValues {
                     fc: value.fc_pp,
                     max: value.fc_pp,
                 },
+                leaderboard: GosuLeaderboard {
+                    has_leaderboard: !value.leaderboard.is_empty(),
+                    our_player: value
+                        .leaderboard
+                        .iter()
+                        .find(|v| v.is_ourself)
+                        .map(GosuLeaderboardPlayer::from),
+                    slots: value
+                        .leaderboard
+                        .iter()
+                        .map(GosuLeaderboardPlayer::from)
+                        .collect(),
+                },
+            },
+            user_profile: GosuUserProfile {
+                name: value.user_profile.name.clone(),
+                id: value.user_profile.user_id,
+                accuracy: value.user_profile.accuracy,
+                ranked_score: value.user_profile.ranked_score,
+                level: value.user_profile.level,
+                play_count: value.user_profile.play_count,
+                play_mode: value.user_profile.play_mode,
+                rank: value.user_profile.rank,
+                pp: value.user_profile.pp,
+                bancho_status: value.user_profile.bancho_status,
+                country_code: value.user_profile.country_code,
             },
         }
     }
@@ -0,0 +1,212 @@
+//! Fields of osu! objects that are read together, see [`Layout`]
+//!
+//! [`Layout`]: crate::memory::Layout
+
+/// `Beatmap`, pointers are pointing to C# strings
+pub mod beatmap {
+    use crate::memory::{Field, Layout};
+
+    pub const ARTIST: Field<i32> = Field::new(0x18);
+    pub const TITLE: Field<i32> = Field::new(0x24);
+    pub const AR: Field<f32> = Field::new(0x2C);
+    pub const CS: Field<f32> = Field::new(0x30);
+    pub const HP: Field<f32> = Field::new(0x34);
+    pub const OD: Field<f32> = Field::new(0x38);
+    pub const AUDIO_FILE: Field<i32> = Field::new(0x64);
+    pub const MD5: Field<i32> = Field::new(0x6C);
+    pub const FOLDER: Field<i32> = Field::new(0x78);
+    pub const CREATOR: Field<i32> = Field::new(0x7C);
+    pub const FILE: Field<i32> = Field::new(0x90);
+    pub const DIFFICULTY: Field<i32> = Field::new(0xAC);
+    pub const MAP_ID: Field<i32> = Field::new(0xC8);
+    pub const MAPSET_ID: Field<i32> = Field::new(0xCC);
+    pub const STATUS: Field<i16> = Field::new(0x12C);
+
+    pub const LAYOUT: Layout = Layout::new(&[
+        ARTIST.span(),
+        TITLE.span(),
+        AR.span(),
+        CS.span(),
+        HP.span(),
+        OD.span(),
+        AUDIO_FILE.span(),
+        MD5.span(),
+        FOLDER.span(),
+        CREATOR.span(),
+        FILE.span(),
+        DIFFICULTY.span(),
+        MAP_ID.span(),
+        MAPSET_ID.span(),
+        STATUS.span(),
+    ]);
+}
+
+/// `Score`, used both during gameplay and on the result screen
+pub mod score {
+    use crate::memory::{Field, Layout};
+
+    /// Pointer to [`super::mods`]
+    pub const MODS: Field<i32> = Field::new(0x1C);
+
+    /// Pointer to C# string
+    pub const USERNAME: Field<i32> = Field::new(0x28);
+
+    /// Pointer to C# `List<int>`
+    pub const HIT_ERRORS: Field<i32> = Field::new(0x38);
+
+    pub const MODE: Field<i32> = Field::new(0x64);
+    pub const MAX_COMBO: Field<i16> = Field::new(0x68);
+    pub const SCORE: Field<i32> = Field::new(0x78);
+    pub const HIT_100: Field<i16> = Field::new(0x88);
+    pub const HIT_300: Field<i16> = Field::new(0x8A);
+    pub const HIT_50: Field<i16> = Field::new(0x8C);
+    pub const HIT_GEKI: Field<i16> = Field::new(0x8E);
+    pub const HIT_KATU: Field<i16> = Field::new(0x90);
+    pub const HIT_MISS: Field<i16> = Field::new(0x92);
+    pub const COMBO: Field<i16> = Field::new(0x94);
+
+    pub const LAYOUT: Layout = Layout::new(&[
+        MODS.span(),
+        USERNAME.span(),
+        HIT_ERRORS.span(),
+        MODE.span(),
+        MAX_COMBO.span(),
+        SCORE.span(),
+        HIT_100.span(),
+        HIT_300.span(),
+        HIT_50.span(),
+        HIT_GEKI.span(),
+        HIT_KATU.span(),
+        HIT_MISS.span(),
+        COMBO.span(),
+    ]);
+}
+
+/// Obfuscated mods, actual mods are `XOR1 ^ XOR2`
+pub mod mods {
+    use crate::memory::{Field, Layout};
+
+    pub const XOR1: Field<u32> = Field::new(0xC);
+    pub const XOR2: Field<u32> = Field::new(0x8);
+
+    pub const LAYOUT: Layout = Layout::new(&[XOR1.span(), XOR2.span()]);
+}
+
+/// Key overlay, pointers are pointing to [`super::key`]
+pub mod key_overlay {
+    use crate::memory::{Field, Layout};
+
+    pub const K1: Field<i32> = Field::new(0x8);
+    pub const K2: Field<i32> = Field::new(0xC);
+    pub const M1: Field<i32> = Field::new(0x10);
+    pub const M2: Field<i32> = Field::new(0x14);
+
+    pub const LAYOUT: Layout = Layout::new(&[K1.span(), K2.span(), M1.span(), M2.span()]);
+}
+
+/// Single key of the key overlay
+pub mod key {
+    use crate::memory::{Field, Layout};
+
+    pub const COUNT: Field<i32> = Field::new(0x14);
+    pub const PRESSED: Field<i8> = Field::new(0x1C);
+
+    pub const LAYOUT: Layout = Layout::new(&[COUNT.span(), PRESSED.span()]);
+}
+
+/// In-game leaderboard, located at `[[ruleset + 0x7C] + 0x24]`
+pub mod leaderboard {
+    use crate::memory::{Field, Layout};
+
+    /// Pointer to C# `List` of [`super::leaderboard_player`]
+    pub const PLAYERS: Field<i32> = Field::new(0x4);
+
+    /// Pointer to [`super::leaderboard_player`] of ourselves
+    pub const OUR_PLAYER: Field<i32> = Field::new(0x10);
+
+    pub const LAYOUT: Layout = Layout::new(&[PLAYERS.span(), OUR_PLAYER.span()]);
+}
+
+/// Slot of the leaderboard
+pub mod leaderboard_player {
+    use crate::memory::{Field, Layout};
+
+    /// Pointer to C# string
+    pub const NAME: Field<i32> = Field::new(0x8);
+
+    /// Pointer to [`super::score`], zero when slot is empty
+    pub const SCORE_ENTRY: Field<i32> = Field::new(0x20);
+
+    pub const POSITION: Field<i32> = Field::new(0x2C);
+    pub const SCORE: Field<i32> = Field::new(0x30);
+    pub const TEAM: Field<i32> = Field::new(0x40);
+    pub const IS_PASSING: Field<i8> = Field::new(0x4B);
+
+    pub const LAYOUT: Layout = Layout::new(&[
+        NAME.span(),
+        SCORE_ENTRY.span(),
+        POSITION.span(),
+        SCORE.span(),
+        TEAM.span(),
+        IS_PASSING.span(),
+    ]);
+}
+
+/// C# `Dictionary<TKey, TValue>`
+pub mod dictionary {
+    use crate::memory::{Field, Layout};
+
+    /// Pointer to array of [`super::dictionary_entry`]
+    pub const ENTRIES: Field<i32> = Field::new(0x8);
+
+    /// Amount of used entries, including removed ones
+    pub const COUNT: Field<i32> = Field::new(0x1C);
+
+    pub const LAYOUT: Layout = Layout::new(&[ENTRIES.span(), COUNT.span()]);
+}
+
+/// Entry of [`super::dictionary`] with reference key
+/// and value, entries are `0x10` bytes apart
+pub mod dictionary_entry {
+    use crate::memory::{Field, Layout};
+
+    /// Zero when entry is removed
+    pub const KEY: Field<i32> = Field::new(0x0);
+    pub const VALUE: Field<i32> = Field::new(0x4);
+
+    pub const LAYOUT: Layout = Layout::new(&[KEY.span(), VALUE.span()]);
+}
+
+/// Bancho user panel of the logged in player
+pub mod user_profile {
+    use crate::memory::{Field, Layout};
+
+    pub const ACCURACY: Field<f64> = Field::new(0x4);
+    pub const RANKED_SCORE: Field<i64> = Field::new(0xC);
+
+    /// Pointer to C# string
+    pub const NAME: Field<i32> = Field::new(0x30);
+
+    pub const USER_ID: Field<i32> = Field::new(0x70);
+    pub const LEVEL: Field<f32> = Field::new(0x74);
+    pub const PLAY_COUNT: Field<i32> = Field::new(0x7C);
+    pub const PLAY_MODE: Field<i32> = Field::new(0x80);
+    pub const RANK: Field<i32> = Field::new(0x84);
+    pub const PP: Field<i16> = Field::new(0x88);
+    pub const BANCHO_STATUS: Field<u8> = Field::new(0x8C);
+    pub const COUNTRY_CODE: Field<i32> = Field::new(0x9C);
+
+    pub const LAYOUT: Layout = Layout::new(&[
+        ACCURACY.span(),
+        RANKED_SCORE.span(),
+        NAME.span(),
+        USER_ID.span(),
+        LEVEL.span(),
+        PLAY_COUNT.span(),
+        PLAY_MODE.span(),
+        RANK.span(),
+        PP.span(),
+        BANCHO_STATUS.span(),
+        COUNTRY_CODE.span(),
+    ]);
+}
@@ -1 +1,16 @@
+pub mod config;
+pub mod events;
+pub mod gosu_structs;
+mod layouts;
+pub mod memory;
+pub mod metrics;
+pub mod network;
+pub mod reader;
+pub mod reading_loop;
+pub mod session;
+pub mod skin;
+pub mod structs;
+mod utils;
 
+pub use reader::{Reader, ReaderEvent};
+pub use structs::{BeatmapValues, GameplayValues, OutputValues};
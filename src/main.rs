@@ -1,35 +1,33 @@
-mod gosu_structs;
-mod network;
-mod reading_loop;
-mod structs;
-mod utils;
-
-use structs::{Clients, InnerValues, OutputValues};
-
-use crate::network::{handle_clients, server_thread};
-
-use crate::reading_loop::process_reading_loop;
-use crate::structs::{State, StaticAddresses};
+use rosu_memory::{
+    config::{self, Config},
+    metrics::OsuPathSource,
+    network::{
+        broadcast::{EVENT_QUEUE_SIZE, FrameQueue, publish, publish_event, publish_precise},
+        server_thread,
+    },
+    reader::{Reader, ReaderEvent},
+    session::{self, Recorder},
+};
 
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 
-use clap::Parser;
-
-use rosu_mem::{
-    error::ProcessError,
-    process::{Process, ProcessTraits},
-};
-
-use eyre::{Report, Result};
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, parser::ValueSource};
 
-// Workaround for new winello umu-run stuff
-static EXCLUDE_WORDS: [&str; 2] = ["umu-run", "waitforexitandrun"];
+use eyre::Result;
 
-#[derive(Parser, Debug)]
+/// Every argument except for `--config` overrides
+/// the same value of the config file when passed
+#[derive(Parser, Debug, Clone)]
 pub struct Args {
+    /// Path to the config file,
+    /// `config.toml` next to the executable by default
+    #[arg(short, long, env = "ROSU_CONFIG")]
+    config: Option<PathBuf>,
+
     /// Path to osu! folder
     #[arg(short, long, env)]
     osu_path: Option<PathBuf>,
@@ -39,11 +37,138 @@ pub struct Args {
     #[arg(short, long, value_parser=parse_interval_ms)]
     interval: std::time::Duration,
 
+    /// Interval between updates while playing in ms
+    #[arg(long, value_parser=parse_interval_ms)]
+    playing_interval: Option<std::time::Duration>,
+
+    /// Interval between updates in song select in ms
+    #[arg(long, value_parser=parse_interval_ms)]
+    song_select_interval: Option<std::time::Duration>,
+
+    /// Interval between updates in main menu in ms
+    #[arg(long, value_parser=parse_interval_ms)]
+    idle_interval: Option<std::time::Duration>,
+
+    /// Interval of reading key overlay and hit errors
+    /// between updates in ms, 0 (default) disables it
+    #[arg(long, value_parser=parse_interval_ms)]
+    precise_interval: Option<std::time::Duration>,
+
     /// Amount of seconds waiting after critical error happened
     /// before running again
     #[clap(default_value = "3")]
     #[arg(short, long, value_parser=parse_interval_secs)]
     error_interval: std::time::Duration,
+
+    /// Address to listen on
+    #[clap(default_value = "127.0.0.1")]
+    #[arg(long, env = "ROSU_HOST")]
+    host: IpAddr,
+
+    /// Port to listen on
+    #[clap(default_value = "24050")]
+    #[arg(short, long, env = "ROSU_PORT")]
+    port: u16,
+
+    /// Additional addresses to listen on, comma separated
+    /// Example: `192.168.1.10:24050,127.0.0.1:24051`
+    #[arg(short, long, env = "ROSU_BIND", value_delimiter = ',')]
+    bind: Vec<SocketAddr>,
+
+    /// Record every tick into the session file
+    #[arg(long, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+
+    /// Replay session file instead of reading osu! memory
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Replay speed multiplier
+    #[clap(default_value = "1.0")]
+    #[arg(long, value_parser=parse_speed)]
+    replay_speed: f64,
+
+    /// Start replay from this point of the session in ms
+    #[clap(default_value = "0")]
+    #[arg(long, value_parser=parse_interval_ms)]
+    replay_seek: std::time::Duration,
+
+    /// Start replay over again after reaching the end
+    #[arg(long)]
+    replay_loop: bool,
+
+    /// Folder with counters (overlays) to serve,
+    /// `counters` folder next to the executable by default
+    #[arg(short, long, env = "ROSU_STATIC_DIR")]
+    static_dir: Option<PathBuf>,
+}
+
+impl Args {
+    fn config_path(&self) -> Option<PathBuf> {
+        if let Some(ref path) = self.config {
+            return Some(path.clone());
+        }
+
+        std::env::current_exe()
+            .ok()
+            .and_then(|v| v.parent().map(|v| v.join("config.toml")))
+            .filter(|v| v.is_file())
+    }
+
+    /// Overwrites config values with arguments passed
+    /// through command line or environment variables
+    fn apply(&self, matches: &ArgMatches, config: &mut Config) {
+        let explicit = |id| {
+            matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+            )
+        };
+
+        if explicit("interval") {
+            config.interval = self.interval.as_millis() as u64;
+        }
+
+        if let Some(v) = self.playing_interval {
+            config.intervals.playing = Some(v.as_millis() as u64);
+        }
+
+        if let Some(v) = self.song_select_interval {
+            config.intervals.song_select = Some(v.as_millis() as u64);
+        }
+
+        if let Some(v) = self.idle_interval {
+            config.intervals.idle = Some(v.as_millis() as u64);
+        }
+
+        if let Some(v) = self.precise_interval {
+            config.precise_interval = v.as_millis() as u64;
+        }
+
+        if explicit("error_interval") {
+            config.error_interval = self.error_interval.as_secs();
+        }
+
+        if explicit("host") {
+            config.host = self.host;
+        }
+
+        if explicit("port") {
+            config.port = self.port;
+        }
+
+        if explicit("bind") {
+            config.bind.clone_from(&self.bind);
+        }
+
+        if self.osu_path.is_some() {
+            config.osu_path.clone_from(&self.osu_path);
+        }
+
+        if self.static_dir.is_some() {
+            config.static_dir.clone_from(&self.static_dir);
+        }
+    }
 }
 
 fn parse_interval_ms(arg: &str) -> Result<std::time::Duration, std::num::ParseIntError> {
@@ -56,132 +181,180 @@ fn parse_interval_secs(arg: &str) -> Result<std::time::Duration, std::num::Parse
     Ok(std::time::Duration::from_secs(secs))
 }
 
+fn parse_speed(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(v) if v > 0.0 && v.is_finite() => Ok(v),
+        Ok(_) => Err("speed should be a positive number".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
 fn main() -> Result<()> {
     let _client = tracy_client::Client::start();
 
-    let args = Args::parse();
-    let output_values = Arc::new(Mutex::new(OutputValues::default()));
-    let inner_values = InnerValues::default();
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let config_path = args.config_path();
 
-    let mut state = State {
-        addresses: StaticAddresses::default(),
-        clients: Clients::default(),
-        ivalues: inner_values,
-        values: output_values,
+    let mut config = match config_path {
+        Some(ref path) => {
+            println!("Using config: {}", path.display());
+            Config::load(path)?
+        }
+        None => Config::default(),
     };
 
+    args.apply(&matches, &mut config);
+
+    let mut reader = Reader::new(
+        config.osu_path.clone(),
+        config.interval(),
+        config.error_interval(),
+    );
+    let snapshots = FrameQueue::default();
+    let events = FrameQueue::new(EVENT_QUEUE_SIZE);
+    let precise = FrameQueue::default();
+
+    let static_dir = config.static_dir();
+    if static_dir.is_dir() {
+        println!("Serving counters from: {}", static_dir.display());
+    } else {
+        println!("Counters folder doesn't exist: {}", static_dir.display());
+    }
+
+    let config = Arc::new(Mutex::new(config));
+
+    if let Some(path) = config_path {
+        let config = config.clone();
+        let args = args.clone();
+
+        thread::spawn(move || {
+            config::watch(&path, config, |config| args.apply(&matches, config))
+        });
+    }
+
     // Spawning Hyper server
-    let server_clients = state.clients.clone();
-    let server_values = state.values.clone();
-    std::thread::spawn(move || server_thread(server_clients, server_values));
+    let server_values = reader.values();
+    let server_snapshots = snapshots.receiver();
+    let server_events = events.receiver();
+    let server_precise = precise.receiver();
+    let server_metrics = reader.metrics();
+    let server_config = config.clone();
+
+    std::thread::spawn(move || {
+        server_thread(
+            server_values,
+            server_snapshots,
+            server_events,
+            server_precise,
+            server_metrics,
+            server_config,
+        )
+    });
 
     println!("Spawned server!");
 
-    if args.interval != Duration::from_millis(300) {
-        println!("Using non default interval: {}", args.interval.as_millis());
-    }
+    if let Some(ref path) = args.replay {
+        println!("Replaying session: {}", path.display());
 
-    'init_loop: loop {
-        let p = match Process::initialize("osu!.exe", &EXCLUDE_WORDS) {
-            Ok(p) => {
-                println!("Found process, pid - {}", p.pid);
-                p
-            }
-            Err(e) => {
-                println!("{:?}", Report::new(e));
-                thread::sleep(args.error_interval);
-                continue 'init_loop;
-            }
-        };
+        loop {
+            session::replay(path, args.replay_speed, args.replay_seek, &snapshots)?;
 
-        let mut values = state.values.lock().unwrap();
-        // OSU_PATH cli argument if provided should
-        // overwrite auto detected path
-        // else use auto detected path
-        match args.osu_path {
-            Some(ref v) => {
-                println!("Using provided osu! folder path");
-                values.osu_path.clone_from(v);
-            }
-            None => {
-                println!("Using auto-detected osu! folder path");
-                if let Some(ref dir) = p.executable_dir {
-                    values.osu_path.clone_from(dir);
-                } else {
-                    return Err(Report::msg(
-                        "Can't auto-detect osu! folder path \
-                         nor any was provided through command \
-                         line argument",
-                    ));
-                }
+            if !args.replay_loop {
+                break;
             }
         }
 
-        // Checking if path exists
-        if !values.osu_path.exists() {
-            println!(
-                "Provided osu path doesn't exists!\n Path: {}",
-                &values.osu_path.to_str().unwrap()
-            );
+        // Giving clients a moment to receive the last tick
+        thread::sleep(config.lock().unwrap().interval());
+        println!("Replay finished");
 
-            return Err(Report::msg(
-                "Can't auto-detect osu! folder path \
-                 nor any was provided through command \
-                 line argument",
-            ));
+        return Ok(());
+    }
+
+    let mut recorder = match args.record {
+        Some(ref path) => {
+            println!("Recording session into: {}", path.display());
+            Some(Recorder::new(path)?)
+        }
+        None => None,
+    };
+
+    let interval = config.lock().unwrap().interval();
+    if interval != Duration::from_millis(300) {
+        println!("Using non default interval: {}", interval.as_millis());
+    }
+
+    let values = reader.values();
+
+    loop {
+        // Config could be reloaded since the last event
+        let logging = {
+            let config = config.lock().unwrap();
+            reader.set_intervals(config.interval(), config.error_interval());
+            reader.set_state_intervals(config.state_intervals());
+
+            // Nobody can receive precise values without the endpoint
+            reader.set_precise_interval(if config.endpoints.precise {
+                config.precise_interval()
+            } else {
+                None
+            });
+
+            config.logging
         };
 
-        drop(values);
+        let Some(event) = reader.next() else {
+            break;
+        };
 
-        println!("Reading static signatures...");
-        match StaticAddresses::new(&p) {
-            Ok(v) => state.addresses = v,
-            Err(e) => match e.downcast_ref::<ProcessError>() {
-                Some(&ProcessError::ProcessNotFound) => {
-                    thread::sleep(args.error_interval);
-                    continue 'init_loop;
+        match event? {
+            ReaderEvent::Attached {
+                pid,
+                osu_path,
+                osu_path_source,
+            } => {
+                match osu_path_source {
+                    OsuPathSource::Cli => println!("Using provided osu! folder path"),
+                    OsuPathSource::Auto => println!("Using auto-detected osu! folder path"),
                 }
-                #[cfg(target_os = "windows")]
-                Some(&ProcessError::OsError { .. }) => {
-                    println!("{:?}", e);
-                    thread::sleep(args.error_interval);
-                    continue 'init_loop;
+
+                println!("osu! folder: {}", osu_path.display());
+                println!("Found process, pid - {}", pid);
+                println!("Starting reading loop");
+            }
+            ReaderEvent::Detached => println!("Lost osu! process"),
+            ReaderEvent::Tick(raw) => {
+                let snapshot = publish(&values, &snapshots);
+
+                if let Some(ref mut recorder) = recorder
+                    && let Err(e) = recorder.record(&raw, &snapshot)
+                {
+                    println!("Failed to record tick: {:?}", e);
                 }
-                Some(_) | None => {
-                    println!("{:?}", e);
-                    thread::sleep(args.error_interval);
-                    continue 'init_loop;
+            }
+            ReaderEvent::Precise(values) => publish_precise(&values, &precise),
+            ReaderEvent::Game(event) => {
+                if logging.events {
+                    println!("Event: {}", event.name());
                 }
-            },
-        };
 
-        println!("Starting reading loop");
-        'main_loop: loop {
-            if let Err(e) = process_reading_loop(&p, &mut state) {
-                match e.downcast_ref::<ProcessError>() {
-                    Some(&ProcessError::ProcessNotFound) => {
-                        thread::sleep(args.error_interval);
-                        continue 'init_loop;
-                    }
-                    #[cfg(target_os = "windows")]
-                    Some(&ProcessError::OsError { .. }) => {
-                        println!("{:?}", e);
-                        thread::sleep(args.error_interval);
-                        continue 'init_loop;
-                    }
-                    Some(_) | None => {
-                        println!("{:?}", e);
-                        thread::sleep(args.error_interval);
-                        continue 'main_loop;
-                    }
+                publish_event(&event, &events);
+            }
+            ReaderEvent::Error(e) => {
+                if logging.errors {
+                    println!("{:?}", e);
+                }
+            }
+            ReaderEvent::Skipped { section, error } => {
+                if logging.errors {
+                    println!("{:?}", error);
+                    println!("Skipped {} reading", section.name());
                 }
             }
-
-            smol::block_on(async {
-                handle_clients(state.values.clone(), state.clients.clone()).await;
-            });
-
-            std::thread::sleep(args.interval);
         }
     }
+
+    Ok(())
 }
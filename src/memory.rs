@@ -0,0 +1,444 @@
+use std::marker::PhantomData;
+
+use rosu_mem::{
+    error::ProcessError,
+    process::{Process, ProcessTraits},
+    signature::Signature,
+};
+
+macro_rules! prim_read_impl {
+    ($name: ident, $t: ty) => {
+        fn $name(&self, addr: i32) -> Result<$t, ProcessError> {
+            let mut bytes = [0u8; size_of::<$t>()];
+            self.read(addr, size_of::<$t>(), &mut bytes)?;
+
+            Ok(<$t>::from_le_bytes(bytes))
+        }
+    };
+}
+
+/// Value that can be decoded from little endian bytes
+pub trait Primitive: Sized {
+    const SIZE: usize;
+
+    fn from_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! primitive_impl {
+    ($($t: ty),*) => {$(
+        impl Primitive for $t {
+            const SIZE: usize = size_of::<$t>();
+
+            fn from_le(bytes: &[u8]) -> Self {
+                let mut buff = [0u8; size_of::<$t>()];
+                buff.copy_from_slice(bytes);
+
+                <$t>::from_le_bytes(buff)
+            }
+        }
+    )*};
+}
+
+primitive_impl!(i8, u8, i16, i32, u32, i64, u64, f32, f64);
+
+/// Field of type `T` located at `offset` from the object base
+pub struct Field<T> {
+    pub offset: i32,
+    _type: PhantomData<T>,
+}
+
+impl<T> Clone for Field<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Field<T> {}
+
+impl<T: Primitive> Field<T> {
+    pub const fn new(offset: i32) -> Self {
+        Self {
+            offset,
+            _type: PhantomData,
+        }
+    }
+
+    /// Bytes the field occupies, `(start, end)`
+    pub const fn span(self) -> (i32, i32) {
+        (self.offset, self.offset + T::SIZE as i32)
+    }
+}
+
+/// Region of the object that covers all of its fields,
+/// read with a single [`MemoryReader::read`]
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+    start: i32,
+    end: i32,
+}
+
+impl Layout {
+    /// Built from [`Field::span`] of every field
+    pub const fn new(spans: &[(i32, i32)]) -> Self {
+        let mut layout = Self {
+            start: i32::MAX,
+            end: i32::MIN,
+        };
+
+        let mut i = 0;
+        while i < spans.len() {
+            if spans[i].0 < layout.start {
+                layout.start = spans[i].0;
+            }
+
+            if spans[i].1 > layout.end {
+                layout.end = spans[i].1;
+            }
+
+            i += 1;
+        }
+
+        layout
+    }
+
+    /// Amount of bytes the layout covers
+    pub const fn size(&self) -> usize {
+        (self.end - self.start) as usize
+    }
+}
+
+/// Object bytes read according to its [`Layout`]
+pub struct Batch {
+    start: i32,
+    bytes: Vec<u8>,
+}
+
+impl Batch {
+    /// Panics if field is not covered by the layout
+    /// batch was read with
+    pub fn get<T: Primitive>(&self, field: Field<T>) -> T {
+        let at = (field.offset - self.start) as usize;
+
+        T::from_le(&self.bytes[at..at + T::SIZE])
+    }
+}
+
+/// Source of osu! memory. Everything inside reading loop
+/// is read through this trait, so it can be driven by
+/// something other than real process
+///
+/// Only [`MemoryReader::read`] and [`MemoryReader::read_signature`]
+/// are required, everything else is built on top of them
+/// and mirrors [`ProcessTraits`] behaviour
+pub trait MemoryReader {
+    fn read(&self, addr: i32, len: usize, buff: &mut [u8]) -> Result<(), ProcessError>;
+
+    fn read_signature(&self, sign: &Signature) -> Result<i32, ProcessError>;
+
+    prim_read_impl!(read_i8, i8);
+    prim_read_impl!(read_i16, i16);
+    prim_read_impl!(read_i32, i32);
+    prim_read_impl!(read_u32, u32);
+    prim_read_impl!(read_u64, u64);
+    prim_read_impl!(read_f64, f64);
+
+    /// Reads every field of the `layout` of an object
+    /// located at `base` in one go
+    fn read_batch(&self, base: i32, layout: &Layout) -> Result<Batch, ProcessError> {
+        let mut bytes = vec![0u8; layout.size()];
+        self.read(base + layout.start, bytes.len(), &mut bytes)?;
+
+        Ok(Batch {
+            start: layout.start,
+            bytes,
+        })
+    }
+
+    /// Reads C# `List<int>` located at `addr`
+    fn read_i32_array(&self, addr: i32, buff: &mut Vec<i32>) -> Result<(), ProcessError> {
+        let items_ptr = self.read_i32(addr + 0x4)?;
+        let size = self.read_i32(addr + 0xC)? as usize;
+
+        buff.resize(size, 0);
+
+        let mut byte_buff = vec![0u8; size * size_of::<i32>()];
+        self.read(items_ptr + 0x8, byte_buff.len(), &mut byte_buff)?;
+
+        for (value, bytes) in buff.iter_mut().zip(byte_buff.chunks_exact(4)) {
+            // Safety: `chunks_exact` guarantees length of 4
+            *value = i32::from_le_bytes(unsafe { bytes.try_into().unwrap_unchecked() });
+        }
+
+        Ok(())
+    }
+
+    /// Appends items of C# `List<int>` located at `addr` that are not
+    /// in `buff` yet, starts over if list got shorter than `buff`.
+    /// Returns index of the first appended item
+    fn read_i32_array_append(&self, addr: i32, buff: &mut Vec<i32>) -> Result<usize, ProcessError> {
+        let items_ptr = self.read_i32(addr + 0x4)?;
+        let size = self.read_i32(addr + 0xC)? as usize;
+
+        if size < buff.len() {
+            buff.clear();
+        }
+
+        let start = buff.len();
+
+        let mut byte_buff = vec![0u8; (size - start) * size_of::<i32>()];
+        self.read(
+            items_ptr + 0x8 + (start * size_of::<i32>()) as i32,
+            byte_buff.len(),
+            &mut byte_buff,
+        )?;
+
+        buff.extend(byte_buff.chunks_exact(4).map(|bytes| {
+            // Safety: `chunks_exact` guarantees length of 4
+            i32::from_le_bytes(unsafe { bytes.try_into().unwrap_unchecked() })
+        }));
+
+        Ok(start)
+    }
+
+    /// Reads only the last `count` items of C# `List<int>` located
+    /// at `addr`, returns total amount of items in the list
+    fn read_i32_array_tail(
+        &self,
+        addr: i32,
+        count: usize,
+        buff: &mut Vec<i32>,
+    ) -> Result<usize, ProcessError> {
+        let items_ptr = self.read_i32(addr + 0x4)?;
+        let size = self.read_i32(addr + 0xC)? as usize;
+        let start = size.saturating_sub(count);
+
+        buff.resize(size - start, 0);
+
+        let mut byte_buff = vec![0u8; buff.len() * size_of::<i32>()];
+        self.read(
+            items_ptr + 0x8 + (start * size_of::<i32>()) as i32,
+            byte_buff.len(),
+            &mut byte_buff,
+        )?;
+
+        for (value, bytes) in buff.iter_mut().zip(byte_buff.chunks_exact(4)) {
+            // Safety: `chunks_exact` guarantees length of 4
+            *value = i32::from_le_bytes(unsafe { bytes.try_into().unwrap_unchecked() });
+        }
+
+        Ok(size)
+    }
+
+    /// Reads C# string which pointer is located at `addr`
+    ///
+    /// Throws [`ProcessError::StringTooLarge`] if string length
+    /// is over a provided limit
+    fn read_string_with_limit_from_ptr(
+        &self,
+        addr: i32,
+        limit: usize,
+    ) -> Result<String, ProcessError> {
+        self.read_string_with_limit(self.read_i32(addr)?, limit)
+    }
+
+    /// Reads C# string located at `addr`
+    ///
+    /// Throws [`ProcessError::StringTooLarge`] if string length
+    /// is over a provided limit
+    fn read_string_with_limit(&self, addr: i32, limit: usize) -> Result<String, ProcessError> {
+        // C# string structure: 4B obj header, 4B str len, str itself
+        let len = self.read_u32(addr + 0x4)? as usize;
+
+        if len > limit {
+            return Err(ProcessError::StringTooLarge);
+        }
+
+        let mut byte_buff = vec![0u8; len * 2];
+        self.read(addr + 0x8, byte_buff.len(), &mut byte_buff)?;
+
+        let buff: Vec<u16> = byte_buff
+            .chunks_exact(2)
+            .map(|v| u16::from_le_bytes([v[0], v[1]]))
+            .collect();
+
+        Ok(String::from_utf16_lossy(&buff))
+    }
+}
+
+impl MemoryReader for Process {
+    #[inline]
+    fn read(&self, addr: i32, len: usize, buff: &mut [u8]) -> Result<(), ProcessError> {
+        ProcessTraits::read(self, addr, len, buff)
+    }
+
+    #[inline]
+    fn read_signature(&self, sign: &Signature) -> Result<i32, ProcessError> {
+        ProcessTraits::read_signature(self, sign)
+    }
+}
+
+/// In-memory image of the process, addresses are mapped
+/// to individual bytes and signatures are mapped directly
+/// to their addresses. Reading unmapped byte results in
+/// [`ProcessError::BadAddress`]
+#[cfg(test)]
+#[derive(Default)]
+pub struct FakeMemory {
+    bytes: std::collections::HashMap<i32, u8>,
+    signatures: std::collections::HashMap<String, i32>,
+}
+
+#[cfg(test)]
+impl FakeMemory {
+    pub fn write(&mut self, addr: i32, bytes: &[u8]) -> &mut Self {
+        for (i, byte) in bytes.iter().enumerate() {
+            self.bytes.insert(addr + i as i32, *byte);
+        }
+
+        self
+    }
+
+    pub fn write_i8(&mut self, addr: i32, value: i8) -> &mut Self {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_i16(&mut self, addr: i32, value: i16) -> &mut Self {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_i32(&mut self, addr: i32, value: i32) -> &mut Self {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_u32(&mut self, addr: i32, value: u32) -> &mut Self {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_u64(&mut self, addr: i32, value: u64) -> &mut Self {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_f32(&mut self, addr: i32, value: f32) -> &mut Self {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    pub fn write_f64(&mut self, addr: i32, value: f64) -> &mut Self {
+        self.write(addr, &value.to_le_bytes())
+    }
+
+    /// Writes C# string object at `addr` and pointer to it at `ptr`
+    pub fn write_string(&mut self, ptr: i32, addr: i32, value: &str) -> &mut Self {
+        let utf16: Vec<u8> = value.encode_utf16().flat_map(u16::to_le_bytes).collect();
+
+        self.write_i32(ptr, addr)
+            .write_i32(addr, 0)
+            .write_u32(addr + 0x4, (utf16.len() / 2) as u32)
+            .write(addr + 0x8, &utf16)
+    }
+
+    /// Writes C# `List<int>` object at `addr` with items at `items_addr`
+    pub fn write_i32_list(&mut self, addr: i32, items_addr: i32, values: &[i32]) -> &mut Self {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        self.write_i32(addr + 0x4, items_addr)
+            .write_i32(addr + 0xC, values.len() as i32)
+            .write(items_addr + 0x8, &bytes)
+    }
+
+    pub fn signature(&mut self, sign: &str, addr: i32) -> &mut Self {
+        self.signatures.insert(sign.to_owned(), addr);
+
+        self
+    }
+
+    pub fn remove_signature(&mut self, sign: &str) -> &mut Self {
+        self.signatures.remove(sign);
+
+        self
+    }
+}
+
+#[cfg(test)]
+impl MemoryReader for FakeMemory {
+    fn read(&self, addr: i32, len: usize, buff: &mut [u8]) -> Result<(), ProcessError> {
+        for (i, byte) in buff[..len].iter_mut().enumerate() {
+            *byte = *self
+                .bytes
+                .get(&(addr + i as i32))
+                .ok_or(ProcessError::BadAddress(addr as usize, len))?;
+        }
+
+        Ok(())
+    }
+
+    fn read_signature(&self, sign: &Signature) -> Result<i32, ProcessError> {
+        let sign = sign.to_string();
+
+        self.signatures
+            .get(&sign)
+            .copied()
+            .ok_or(ProcessError::SignatureNotFound(sign))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_fake_memory_reads() {
+        let mut memory = FakeMemory::default();
+
+        memory
+            .write_i32(0x100, -5)
+            .write_f64(0x200, 1.5)
+            .write_string(0x300, 0x400, "osu!")
+            .write_i32_list(0x500, 0x600, &[1, -2, 3]);
+
+        assert_eq!(memory.read_i32(0x100).unwrap(), -5);
+        assert_eq!(memory.read_f64(0x200).unwrap(), 1.5);
+        assert_eq!(
+            memory.read_string_with_limit_from_ptr(0x300, 10).unwrap(),
+            "osu!"
+        );
+
+        assert!(matches!(
+            memory.read_string_with_limit_from_ptr(0x300, 2),
+            Err(ProcessError::StringTooLarge)
+        ));
+
+        let mut list = Vec::new();
+        memory.read_i32_array(0x500, &mut list).unwrap();
+        assert_eq!(list, vec![1, -2, 3]);
+
+        assert!(matches!(
+            memory.read_i32(0x104),
+            Err(ProcessError::BadAddress(..))
+        ));
+    }
+
+    #[test]
+    fn test_read_batch() {
+        const SCORE: Field<i32> = Field::new(0x8);
+        const COMBO: Field<i16> = Field::new(0x10);
+        const HP: Field<f64> = Field::new(0x20);
+        const LAYOUT: Layout = Layout::new(&[HP.span(), SCORE.span(), COMBO.span()]);
+
+        assert_eq!(LAYOUT.size(), 0x20);
+
+        let mut memory = FakeMemory::default();
+        memory
+            .write(0x1008, &[0; 0x20])
+            .write_i32(0x1008, 120917)
+            .write_i16(0x1010, 5)
+            .write_f64(0x1020, 200.0);
+
+        let batch = memory.read_batch(0x1000, &LAYOUT).unwrap();
+        assert_eq!(batch.get(SCORE), 120917);
+        assert_eq!(batch.get(COMBO), 5);
+        assert_eq!(batch.get(HP), 200.0);
+
+        // Region has to be readable as a whole
+        assert!(memory.read_batch(0x1004, &LAYOUT).is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{
+        Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::Report;
+use rosu_mem::error::ProcessError;
+use serde::Serialize;
+
+use crate::structs::SignatureStatus;
+
+/// Upper bounds of tick duration buckets in seconds
+const TICK_BUCKETS: [f64; 10] = [
+    0.0001, 0.00025, 0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1,
+];
+
+/// Where osu! folder path came from
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OsuPathSource {
+    Cli,
+    Auto,
+}
+
+/// What reader is up to, reported by `/status`
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ReaderStatus {
+    /// osu! process, `None` while it's not found
+    pub pid: Option<u32>,
+
+    pub osu_path_source: Option<OsuPathSource>,
+
+    /// Static signatures of the last attach attempt
+    pub signatures: Vec<SignatureStatus>,
+
+    pub last_error: Option<String>,
+}
+
+/// Reader health counters, shared with the server
+/// and rendered in Prometheus text format
+#[derive(Default)]
+pub struct Metrics {
+    /// Ticks per bucket, not cumulative
+    tick_buckets: [AtomicU64; TICK_BUCKETS.len()],
+    ticks: AtomicU64,
+    tick_duration_sum_us: AtomicU64,
+
+    /// Unix timestamp of the last successful tick in ms
+    last_tick_ms: AtomicU64,
+
+    /// By the part of the tick, `tick` for whole tick errors,
+    /// and by `ProcessError` variant, see [`error_kind`]
+    read_errors: Mutex<BTreeMap<(&'static str, &'static str), u64>>,
+
+    beatmap_parse_failures: AtomicU64,
+
+    pub status: Mutex<ReaderStatus>,
+}
+
+impl Metrics {
+    pub fn observe_tick(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+
+        if let Some(i) = TICK_BUCKETS.iter().position(|bound| secs <= *bound) {
+            self.tick_buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+
+        self.ticks.fetch_add(1, Ordering::Relaxed);
+        self.tick_duration_sum_us
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        self.last_tick_ms
+            .store(now.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    pub fn observe_error(&self, section: &'static str, e: &Report) {
+        *self
+            .read_errors
+            .lock()
+            .unwrap()
+            .entry((section, error_kind(e)))
+            .or_default() += 1;
+
+        self.set_last_error(e);
+    }
+
+    pub fn set_last_error(&self, e: &Report) {
+        self.status.lock().unwrap().last_error = Some(format!("{:#}", e));
+    }
+
+    pub fn clear_last_error(&self) {
+        self.status.lock().unwrap().last_error = None;
+    }
+
+    pub fn set_beatmap_parse_failures(&self, count: u64) {
+        self.beatmap_parse_failures.store(count, Ordering::Relaxed);
+    }
+
+    /// Last successful tick, `None` if there were no ticks yet
+    pub fn last_tick(&self) -> Option<SystemTime> {
+        match self.last_tick_ms.load(Ordering::Relaxed) {
+            0 => None,
+            ms => Some(UNIX_EPOCH + Duration::from_millis(ms)),
+        }
+    }
+
+    pub fn render(&self, out: &mut String) {
+        header(
+            out,
+            "rosu_tick_duration_seconds",
+            "histogram",
+            "Reading loop tick duration",
+        );
+
+        let mut cumulative = 0;
+        for (bound, count) in TICK_BUCKETS.iter().zip(&self.tick_buckets) {
+            cumulative += count.load(Ordering::Relaxed);
+            let _ = writeln!(
+                out,
+                "rosu_tick_duration_seconds_bucket{{le=\"{bound}\"}} {cumulative}"
+            );
+        }
+
+        let ticks = self.ticks.load(Ordering::Relaxed);
+        let sum = self.tick_duration_sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+
+        let _ = writeln!(
+            out,
+            "rosu_tick_duration_seconds_bucket{{le=\"+Inf\"}} {ticks}"
+        );
+        let _ = writeln!(out, "rosu_tick_duration_seconds_sum {sum}");
+        let _ = writeln!(out, "rosu_tick_duration_seconds_count {ticks}");
+
+        header(
+            out,
+            "rosu_last_tick_timestamp_seconds",
+            "gauge",
+            "Unix timestamp of the last successful tick",
+        );
+        let _ = writeln!(
+            out,
+            "rosu_last_tick_timestamp_seconds {}",
+            self.last_tick_ms.load(Ordering::Relaxed) as f64 / 1000.0
+        );
+
+        header(
+            out,
+            "rosu_read_errors_total",
+            "counter",
+            "Reading loop errors by kind",
+        );
+        for ((section, kind), count) in self.read_errors.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "rosu_read_errors_total{{section=\"{section}\",kind=\"{kind}\"}} {count}"
+            );
+        }
+
+        header(
+            out,
+            "rosu_beatmap_parse_failures_total",
+            "counter",
+            "Beatmaps that failed to parse",
+        );
+        let _ = writeln!(
+            out,
+            "rosu_beatmap_parse_failures_total {}",
+            self.beatmap_parse_failures.load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// `ProcessError` variant of the error, `Other` for everything else
+pub fn error_kind(e: &Report) -> &'static str {
+    let Some(e) = e.downcast_ref::<ProcessError>() else {
+        return "Other";
+    };
+
+    match e {
+        ProcessError::ProcessNotFound => "ProcessNotFound",
+        ProcessError::ExecutablePathNotFound => "ExecutablePathNotFound",
+        ProcessError::NotEnoughPermissions => "NotEnoughPermissions",
+        ProcessError::IoError(_) => "IoError",
+        ProcessError::FromUtf8Error => "FromUtf8Error",
+        ProcessError::ConvertionError => "ConvertionError",
+        ProcessError::BadAddress(..) => "BadAddress",
+        ProcessError::SignatureNotFound(_) => "SignatureNotFound",
+        ProcessError::AddressConvertError => "AddressConvertError",
+        ProcessError::StringTooLarge => "StringTooLarge",
+        ProcessError::OsError(_) => "OsError",
+    }
+}
+
+/// Writes `# HELP` and `# TYPE` lines of the metric
+pub fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {kind}");
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_render_metrics() {
+        let metrics = Metrics::default();
+
+        metrics.observe_tick(Duration::from_micros(80));
+        metrics.observe_tick(Duration::from_micros(700));
+        metrics.observe_tick(Duration::from_secs(1));
+        metrics.observe_error("tick", &Report::new(ProcessError::BadAddress(0x10, 4)));
+        metrics.observe_error("tick", &Report::new(ProcessError::BadAddress(0x20, 4)));
+        metrics.observe_error("settings", &Report::new(ProcessError::BadAddress(0x20, 4)));
+        metrics.observe_error("tick", &Report::new(ProcessError::StringTooLarge));
+        metrics.observe_error("beatmap", &Report::msg("something else"));
+        metrics.set_beatmap_parse_failures(2);
+
+        let mut out = String::new();
+        metrics.render(&mut out);
+
+        assert!(out.contains("rosu_tick_duration_seconds_bucket{le=\"0.0001\"} 1\n"));
+        assert!(out.contains("rosu_tick_duration_seconds_bucket{le=\"0.0005\"} 1\n"));
+        assert!(out.contains("rosu_tick_duration_seconds_bucket{le=\"0.001\"} 2\n"));
+        assert!(out.contains("rosu_tick_duration_seconds_bucket{le=\"0.1\"} 2\n"));
+        assert!(out.contains("rosu_tick_duration_seconds_bucket{le=\"+Inf\"} 3\n"));
+        assert!(out.contains("rosu_tick_duration_seconds_count 3\n"));
+        assert!(out.contains("rosu_read_errors_total{section=\"tick\",kind=\"BadAddress\"} 2\n"));
+        assert!(
+            out.contains("rosu_read_errors_total{section=\"settings\",kind=\"BadAddress\"} 1\n")
+        );
+        assert!(
+            out.contains("rosu_read_errors_total{section=\"tick\",kind=\"StringTooLarge\"} 1\n")
+        );
+        assert!(out.contains("rosu_read_errors_total{section=\"beatmap\",kind=\"Other\"} 1\n"));
+        assert_eq!(
+            metrics.status.lock().unwrap().last_error.as_deref(),
+            Some("something else")
+        );
+
+        metrics.clear_last_error();
+        assert!(metrics.status.lock().unwrap().last_error.is_none());
+        assert!(out.contains("rosu_beatmap_parse_failures_total 2\n"));
+        assert!(metrics.last_tick().is_some());
+    }
+}
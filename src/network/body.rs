@@ -0,0 +1,20 @@
+use std::{convert::Infallible, io};
+
+use http_body_util::{BodyExt, Empty, Full, combinators::BoxBody};
+use hyper::body::Bytes;
+
+/// Body of every HTTP response, either
+/// in-memory or streamed (files, events)
+pub type Body = BoxBody<Bytes, io::Error>;
+
+pub fn full(bytes: impl Into<Bytes>) -> Body {
+    Full::new(bytes.into()).map_err(infallible).boxed()
+}
+
+pub fn empty() -> Body {
+    Empty::new().map_err(infallible).boxed()
+}
+
+fn infallible(never: Infallible) -> io::Error {
+    match never {}
+}
@@ -0,0 +1,555 @@
+use std::sync::{Arc, OnceLock};
+
+use async_tungstenite::tungstenite::{Bytes, Message, Utf8Bytes};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use smol::{
+    channel::{Receiver, Sender, TrySendError},
+    prelude::*,
+};
+
+use crate::{
+    events::GameEvent,
+    gosu_structs::GosuValues,
+    structs::{Arm, Clients, OutputValues, PreciseValues, WsKind},
+};
+
+use super::{filter::select, patch::merge_patch};
+
+/// Amount of frames client can lag behind before
+/// we start dropping the oldest ones
+pub const CLIENT_QUEUE_SIZE: usize = 2;
+
+/// Unlike snapshots, every event matters,
+/// so events are allowed to pile up a bit more
+pub const EVENT_QUEUE_SIZE: usize = 64;
+
+/// Encoding of values sent to websocket clients
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    #[default]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+    Cbor,
+}
+
+impl Format {
+    /// Name used in `format` query parameter
+    /// and as websocket subprotocol
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Self::Json),
+            "msgpack" => Some(Self::MessagePack),
+            "cbor" => Some(Self::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::MessagePack => "msgpack",
+            Self::Cbor => "cbor",
+        }
+    }
+
+    pub fn message(self, value: &Value) -> Message {
+        match self {
+            Self::Json => Message::Text(serde_json::to_string(value).unwrap().into()),
+            Self::MessagePack | Self::Cbor => Message::Binary(self.binary(value)),
+        }
+    }
+
+    fn binary(self, value: &Value) -> Bytes {
+        let _span = tracy_client::span!("serialize binary");
+
+        match self {
+            Self::Json => serde_json::to_vec(value).unwrap().into(),
+            Self::MessagePack => rmp_serde::to_vec(value).unwrap().into(),
+            Self::Cbor => {
+                let mut buff = Vec::new();
+                ciborium::into_writer(value, &mut buff).unwrap();
+                buff.into()
+            }
+        }
+    }
+}
+
+/// Other representations of the serialized values, only
+/// needed by some clients so they are built on the first use
+#[derive(Default)]
+struct Lazy {
+    value: OnceLock<Value>,
+    msgpack: OnceLock<Bytes>,
+    cbor: OnceLock<Bytes>,
+}
+
+/// Values serialized once per reading loop tick
+/// and shared between all clients
+///
+/// Accessors return `None` for `Events` and `Precise`
+/// clients, they are not interested in snapshots
+pub struct Snapshot {
+    pub rosu: Utf8Bytes,
+    pub gosu: Utf8Bytes,
+
+    rosu_lazy: Lazy,
+    gosu_lazy: Lazy,
+
+    /// All hit errors of the play, `gameplay.hit_errors_new`
+    /// only has the ones added during the last tick
+    hit_errors: Vec<i32>,
+    hit_errors_index: usize,
+    hit_errors_generation: u32,
+}
+
+impl Snapshot {
+    pub fn new(values: &OutputValues) -> Self {
+        let _span = tracy_client::span!("serialize snapshot");
+
+        let gosu_values: GosuValues = values.into();
+
+        Self {
+            hit_errors: values.gameplay.hit_errors.clone(),
+            hit_errors_index: values.gameplay.hit_errors_new.index,
+            hit_errors_generation: values.gameplay.hit_errors_generation,
+            ..Self::from_json(
+                serde_json::to_string(&values).unwrap(),
+                serde_json::to_string(&gosu_values).unwrap(),
+            )
+        }
+    }
+
+    /// Snapshot of already serialized values
+    pub fn from_json(rosu: String, gosu: String) -> Self {
+        Self {
+            rosu: rosu.into(),
+            gosu: gosu.into(),
+            rosu_lazy: Lazy::default(),
+            gosu_lazy: Lazy::default(),
+            hit_errors: Vec::new(),
+            hit_errors_index: 0,
+            hit_errors_generation: 0,
+        }
+    }
+
+    /// JSON and lazily converted values of the endpoint
+    fn parts(&self, kind: WsKind) -> Option<(&Utf8Bytes, &Lazy)> {
+        match kind {
+            WsKind::Gosu => Some((&self.gosu, &self.gosu_lazy)),
+            WsKind::Rosu => Some((&self.rosu, &self.rosu_lazy)),
+            WsKind::Events | WsKind::Precise => None,
+        }
+    }
+
+    /// Serialized JSON as it's sent to the clients
+    pub fn json(&self, kind: WsKind) -> Option<&Utf8Bytes> {
+        self.parts(kind).map(|(json, _)| json)
+    }
+
+    /// JSON parsed back into `Value`, parsed
+    /// only once on the first call
+    pub fn value(&self, kind: WsKind) -> Option<&Value> {
+        let (json, lazy) = self.parts(kind)?;
+
+        Some(
+            lazy.value
+                .get_or_init(|| serde_json::from_str(json.as_str()).unwrap_or_default()),
+        )
+    }
+
+    /// Message in the client's format, binary
+    /// formats are encoded once per snapshot
+    pub fn message(&self, kind: WsKind, format: Format) -> Option<Message> {
+        let (json, lazy) = self.parts(kind)?;
+
+        let binary = match format {
+            Format::Json => return Some(Message::Text(json.clone())),
+            Format::MessagePack => &lazy.msgpack,
+            Format::Cbor => &lazy.cbor,
+        };
+
+        let value = self.value(kind)?;
+        let bytes = binary.get_or_init(|| format.binary(value));
+
+        Some(Message::Binary(bytes.clone()))
+    }
+}
+
+/// Hit errors particular client has received, frames can be
+/// dropped or client can connect in the middle of the play,
+/// so `hit_errors_new` of the snapshot is not always enough
+#[derive(Default)]
+pub struct HitErrorsCursor {
+    sent: usize,
+    generation: u32,
+}
+
+impl HitErrorsCursor {
+    /// Rosu values with `hit_errors_new` continuing from the last
+    /// hit error client has, `None` when snapshot already does that
+    pub fn resync(&mut self, snapshot: &Snapshot) -> Option<Value> {
+        // Client has hit errors of the other play
+        let sent = if self.generation == snapshot.hit_errors_generation {
+            self.sent
+        } else {
+            0
+        };
+
+        self.sent = snapshot.hit_errors.len();
+        self.generation = snapshot.hit_errors_generation;
+
+        if sent == snapshot.hit_errors_index || sent > snapshot.hit_errors.len() {
+            return None;
+        }
+
+        let mut value = snapshot.value(WsKind::Rosu)?.clone();
+        let gameplay = value.get_mut("gameplay")?;
+
+        gameplay["hit_errors_new"] = json!({
+            "index": sent,
+            "values": &snapshot.hit_errors[sent..],
+        });
+
+        Some(value)
+    }
+}
+
+/// Single item of the client's queue
+#[derive(Clone)]
+pub enum Frame {
+    Snapshot(Arc<Snapshot>),
+    Event(EventFrame),
+
+    /// Serialized [`PreciseValues`]
+    Precise(Utf8Bytes),
+}
+
+/// Game event serialized once and shared between all clients
+#[derive(Clone)]
+pub struct EventFrame {
+    pub name: &'static str,
+    pub json: Utf8Bytes,
+}
+
+/// Turns frames into messages for a particular client
+///
+/// In diff mode only the first snapshot is sent as is,
+/// every next one is sent as JSON Merge Patch against the
+/// last sent snapshot, unchanged snapshots are not sent at all
+pub struct Encoder {
+    kind: WsKind,
+    format: Format,
+    diff: bool,
+
+    /// JSON pointers client is subscribed to,
+    /// whole values are sent when empty
+    subscription: Vec<String>,
+
+    /// Last sent values, only kept in diff mode
+    last: Option<Value>,
+
+    /// Only used by rosu clients
+    hit_errors: HitErrorsCursor,
+}
+
+impl Encoder {
+    pub fn new(kind: WsKind, format: Format, diff: bool) -> Self {
+        Self {
+            kind,
+            format,
+            diff,
+            subscription: Vec::new(),
+            last: None,
+            hit_errors: HitErrorsCursor::default(),
+        }
+    }
+
+    /// Replaces current subscription, next
+    /// snapshot is going to be sent in full
+    pub fn subscribe(&mut self, pointers: Vec<String>) {
+        self.subscription = pointers;
+        self.last = None;
+    }
+
+    pub fn encode(&mut self, frame: Frame) -> Option<Message> {
+        let snapshot = match frame {
+            Frame::Event(event) => return Some(Message::Text(event.json)),
+            Frame::Precise(json) if self.format == Format::Json => {
+                return Some(Message::Text(json));
+            }
+            Frame::Precise(json) => {
+                let value = serde_json::from_str(json.as_str()).ok()?;
+                return Some(self.format.message(&value));
+            }
+            Frame::Snapshot(snapshot) => snapshot,
+        };
+
+        let resynced = match self.kind {
+            WsKind::Rosu => self.hit_errors.resync(&snapshot),
+            _ => None,
+        };
+
+        if resynced.is_none() && !self.diff && self.subscription.is_empty() {
+            return snapshot.message(self.kind, self.format);
+        }
+
+        let value = match resynced {
+            Some(value) if self.subscription.is_empty() => value,
+            Some(ref value) => select(value, &self.subscription),
+            None if self.subscription.is_empty() => snapshot.value(self.kind)?.clone(),
+            None => select(snapshot.value(self.kind)?, &self.subscription),
+        };
+
+        if !self.diff {
+            return Some(self.format.message(&value));
+        }
+
+        let message = match self.last {
+            Some(ref last) => {
+                let _span = tracy_client::span!("diff snapshot");
+
+                self.format.message(&merge_patch(last, &value)?)
+            }
+            None => self.format.message(&value),
+        };
+
+        self.last = Some(value);
+
+        Some(message)
+    }
+}
+
+/// Bounded queue that never blocks the producer,
+/// instead the oldest (stale) frame is dropped
+/// to make room for the new one
+pub struct FrameQueue<T> {
+    tx: Sender<T>,
+    rx: Receiver<T>,
+}
+
+impl<T> Clone for FrameQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            rx: self.rx.clone(),
+        }
+    }
+}
+
+impl<T> FrameQueue<T> {
+    pub fn new(size: usize) -> Self {
+        let (tx, rx) = smol::channel::bounded(size);
+
+        Self { tx, rx }
+    }
+
+    /// Returns `false` if receiving side is closed
+    pub fn push(&self, mut value: T) -> bool {
+        loop {
+            match self.tx.try_send(value) {
+                Ok(()) => return true,
+                Err(TrySendError::Full(v)) => {
+                    let _ = self.rx.try_recv();
+                    value = v;
+                }
+                Err(TrySendError::Closed(_)) => return false,
+            }
+        }
+    }
+
+    pub fn receiver(&self) -> Receiver<T> {
+        self.rx.clone()
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+}
+
+impl<T> Default for FrameQueue<T> {
+    /// Queue that holds only the latest value
+    fn default() -> Self {
+        Self::new(1)
+    }
+}
+
+/// Serializes current values and hands them over
+/// to the broadcaster, never waits for clients
+pub fn publish(values: &Arm<OutputValues>, snapshots: &FrameQueue<Arc<Snapshot>>) -> Arc<Snapshot> {
+    let _span = tracy_client::span!("publish snapshot");
+
+    let snapshot = {
+        let values = values.lock().unwrap();
+        Arc::new(Snapshot::new(&values))
+    };
+
+    snapshots.push(snapshot.clone());
+
+    snapshot
+}
+
+/// Serializes game event and hands it over to the broadcaster
+pub fn publish_event(event: &GameEvent, events: &FrameQueue<EventFrame>) {
+    events.push(EventFrame {
+        name: event.name(),
+        json: serde_json::to_string(event).unwrap().into(),
+    });
+}
+
+/// Serializes precise values and hands them over to the broadcaster
+pub fn publish_precise(values: &PreciseValues, precise: &FrameQueue<Utf8Bytes>) {
+    precise.push(serde_json::to_string(values).unwrap().into());
+}
+
+/// Fans out every published snapshot and event into
+/// per-client queues. Clients with closed
+/// queues are removed. Last snapshot is kept in `latest`
+pub async fn broadcaster(
+    snapshots: Receiver<Arc<Snapshot>>,
+    events: Receiver<EventFrame>,
+    precise: Receiver<Utf8Bytes>,
+    clients: Clients,
+    latest: Arm<Option<Arc<Snapshot>>>,
+) {
+    enum Action {
+        Snapshot(Arc<Snapshot>),
+        Event(EventFrame),
+        Precise(Utf8Bytes),
+    }
+
+    loop {
+        let snapshot = async { snapshots.recv().await.map(Action::Snapshot) };
+        let event = async { events.recv().await.map(Action::Event) };
+        let precise = async { precise.recv().await.map(Action::Precise) };
+
+        let Ok(action) = snapshot.or(event).or(precise).await else {
+            break;
+        };
+
+        let mut clients = clients.lock().unwrap();
+
+        match action {
+            Action::Snapshot(snapshot) => {
+                let _span = tracy_client::span!("broadcast snapshot");
+
+                *latest.lock().unwrap() = Some(snapshot.clone());
+
+                clients.retain(|client| {
+                    if matches!(client.kind, WsKind::Events | WsKind::Precise) {
+                        true
+                    } else {
+                        client.queue.push(Frame::Snapshot(snapshot.clone()))
+                    }
+                });
+            }
+            Action::Event(event) => {
+                let _span = tracy_client::span!("broadcast event");
+
+                clients.retain(|client| {
+                    if client.events {
+                        client.queue.push(Frame::Event(event.clone()))
+                    } else {
+                        true
+                    }
+                });
+            }
+            Action::Precise(json) => {
+                let _span = tracy_client::span!("broadcast precise");
+
+                clients.retain(|client| {
+                    if client.kind == WsKind::Precise {
+                        client.queue.push(Frame::Precise(json.clone()))
+                    } else {
+                        true
+                    }
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::structs::HitErrorsNew;
+
+    #[test]
+    fn test_snapshot_formats() {
+        let snapshot = Snapshot::from_json(
+            r#"{"state":2,"gameplay":{"combo":5,"hit_errors":[1,-2]}}"#.to_owned(),
+            r#"{"menu":{"state":2}}"#.to_owned(),
+        );
+
+        let rosu = snapshot.value(WsKind::Rosu).unwrap();
+
+        let Some(Message::Binary(bytes)) = snapshot.message(WsKind::Rosu, Format::MessagePack)
+        else {
+            panic!("expected binary message");
+        };
+        assert_eq!(&rmp_serde::from_slice::<Value>(&bytes).unwrap(), rosu);
+
+        let Some(Message::Binary(bytes)) = snapshot.message(WsKind::Rosu, Format::Cbor) else {
+            panic!("expected binary message");
+        };
+        assert_eq!(
+            &ciborium::from_reader::<Value, _>(&bytes[..]).unwrap(),
+            rosu
+        );
+
+        assert!(matches!(
+            snapshot.message(WsKind::Gosu, Format::Json),
+            Some(Message::Text(text)) if text == snapshot.gosu
+        ));
+        assert!(snapshot.message(WsKind::Events, Format::Json).is_none());
+    }
+
+    #[test]
+    fn test_hit_errors_resync() {
+        let mut values = OutputValues::default();
+        let mut encoder = Encoder::new(WsKind::Rosu, Format::Json, false);
+
+        let mut send = |values: &mut OutputValues, hit_errors: &[i32], index: usize| {
+            values.gameplay.hit_errors = hit_errors.to_vec();
+            values.gameplay.hit_errors_new = HitErrorsNew {
+                index,
+                values: hit_errors[index..].to_vec(),
+            };
+
+            let frame = Frame::Snapshot(Arc::new(Snapshot::new(values)));
+            let Some(Message::Text(text)) = encoder.encode(frame) else {
+                panic!("expected text message");
+            };
+
+            let value: Value = serde_json::from_str(text.as_str()).unwrap();
+            value["gameplay"]["hit_errors_new"].clone()
+        };
+
+        // Connected in the middle of the play
+        values.gameplay.hit_errors_generation = 1;
+        assert_eq!(
+            send(&mut values, &[1, 2, 3, 4], 2),
+            json!({"index": 0, "values": [1, 2, 3, 4]})
+        );
+
+        assert_eq!(
+            send(&mut values, &[1, 2, 3, 4, 5], 4),
+            json!({"index": 4, "values": [5]})
+        );
+
+        // Previous frame was dropped
+        assert_eq!(
+            send(&mut values, &[1, 2, 3, 4, 5, 6, 7], 6),
+            json!({"index": 5, "values": [6, 7]})
+        );
+
+        // Start of the next play was dropped
+        values.gameplay.hit_errors_generation = 2;
+        assert_eq!(
+            send(&mut values, &[9, 8], 1),
+            json!({"index": 0, "values": [9, 8]})
+        );
+    }
+}
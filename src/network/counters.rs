@@ -0,0 +1,109 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::Serialize;
+
+/// Overlay installed into the static folder
+#[derive(Debug, Serialize)]
+pub struct Counter {
+    pub name: String,
+
+    /// Where counter is served from
+    pub url: String,
+
+    /// Parsed `metadata.txt` (tosu & gosumemory format),
+    /// keys are lowercase, e.g. `author`, `version`, `resolution`
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// Lists all folders with `index.html` inside of `dir`
+pub fn list(dir: &Path) -> Vec<Counter> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut counters: Vec<Counter> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().join("index.html").is_file())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+
+            let metadata = fs::read_to_string(entry.path().join("metadata.txt"))
+                .map(|v| parse_metadata(&v))
+                .unwrap_or_default();
+
+            Some(Counter {
+                url: format!("/counters/{}/", percent_encode(&name)),
+                name,
+                metadata,
+            })
+        })
+        .collect();
+
+    counters.sort_by(|a, b| a.name.cmp(&b.name));
+
+    counters
+}
+
+/// Parses `Key: value` lines
+fn parse_metadata(text: &str) -> BTreeMap<String, String> {
+    text.lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_lowercase(), value.trim().to_owned()))
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_list_counters() {
+        let dir = std::env::temp_dir().join(format!("rosu-counters-{}", std::process::id()));
+
+        fs::create_dir_all(dir.join("Maximal Lime")).unwrap();
+        fs::write(dir.join("Maximal Lime/index.html"), "").unwrap();
+        fs::write(
+            dir.join("Maximal Lime/metadata.txt"),
+            "Author: lain\nVersion: 1.0\nResolution: 800x600\ngarbage\n",
+        )
+        .unwrap();
+
+        fs::create_dir_all(dir.join("Overlay")).unwrap();
+        fs::write(dir.join("Overlay/index.html"), "").unwrap();
+
+        // Not a counter
+        fs::create_dir_all(dir.join("fonts")).unwrap();
+        fs::write(dir.join("readme.txt"), "").unwrap();
+
+        let counters = list(&dir);
+
+        assert_eq!(counters.len(), 2);
+
+        assert_eq!(counters[0].name, "Maximal Lime");
+        assert_eq!(counters[0].url, "/counters/Maximal%20Lime/");
+        assert_eq!(counters[0].metadata["author"], "lain");
+        assert_eq!(counters[0].metadata["resolution"], "800x600");
+        assert_eq!(counters[0].metadata.len(), 3);
+
+        assert_eq!(counters[1].name, "Overlay");
+        assert!(counters[1].metadata.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
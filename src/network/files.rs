@@ -0,0 +1,508 @@
+use std::{
+    fs::Metadata,
+    io::{self, ErrorKind, SeekFrom},
+    path::{Component, Path, PathBuf},
+    pin::Pin,
+    task::{Context, Poll},
+    time::UNIX_EPOCH,
+};
+
+use eyre::Result;
+use http_body_util::BodyExt;
+use hyper::{
+    Request, Response, StatusCode,
+    body::{Bytes, Frame, SizeHint},
+    header::{
+        ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE,
+        IF_NONE_MATCH, IF_RANGE, LAST_MODIFIED, RANGE,
+    },
+};
+use smol::{fs::File, io::AsyncSeekExt, prelude::*};
+
+use super::body::{self, Body};
+
+/// Max amount of bytes read from disk at once
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const WEEKDAYS: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Serves file located at `request` path relative to the `root`.
+/// Absolute paths are accepted as well, as long as
+/// they are still inside of the `root`
+pub async fn serve_file<T>(root: &Path, request: &str, req: &Request<T>) -> Result<Response<Body>> {
+    let Some(path) = resolve(root, request) else {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(body::empty())?);
+    };
+
+    let metadata = smol::fs::metadata(&path).await?;
+    let len = metadata.len();
+
+    let last_modified = metadata
+        .modified()
+        .ok()
+        .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+        .map(|v| v.as_secs());
+
+    let etag = etag(&metadata);
+
+    let response = Response::builder()
+        .header(CONTENT_TYPE, mime_type(&path))
+        .header(ACCEPT_RANGES, "bytes")
+        .header(ETAG, &etag);
+
+    let response = match last_modified {
+        Some(secs) => response.header(LAST_MODIFIED, http_date(secs)),
+        None => response,
+    };
+
+    if is_not_modified(req, &etag, last_modified) {
+        return Ok(response
+            .status(StatusCode::NOT_MODIFIED)
+            .body(body::empty())?);
+    }
+
+    let range = if is_range_fresh(req, &etag, last_modified) {
+        let header = req.headers().get(RANGE).and_then(|v| v.to_str().ok());
+        parse_range(header, len)
+    } else {
+        ByteRange::Whole
+    };
+
+    let (response, start, end) = match range {
+        ByteRange::Whole => (response, 0, len),
+        ByteRange::Partial(start, end) => {
+            let response = response.status(StatusCode::PARTIAL_CONTENT).header(
+                CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end - 1, len),
+            );
+
+            (response, start, end)
+        }
+        ByteRange::Unsatisfiable => {
+            return Ok(response
+                .status(StatusCode::RANGE_NOT_SATISFIABLE)
+                .header(CONTENT_RANGE, format!("bytes */{}", len))
+                .body(body::empty())?);
+        }
+    };
+
+    let mut file = File::open(path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+
+    let body = FileBody {
+        file,
+        remaining: end - start,
+        buff: Vec::new(),
+    };
+
+    Ok(response
+        .header(CONTENT_LENGTH, end - start)
+        .body(body.boxed())?)
+}
+
+/// Requested part of the file, `end` is exclusive
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    Whole,
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Parses `Range` header. Invalid header is ignored and
+/// whole file is sent, multiple ranges are not supported
+fn parse_range(header: Option<&str>, len: u64) -> ByteRange {
+    let Some(ranges) = header.and_then(|v| v.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Whole;
+    };
+
+    if ranges.contains(',') {
+        return ByteRange::Unsatisfiable;
+    }
+
+    let Some((start, end)) = ranges.trim().split_once('-') else {
+        return ByteRange::Whole;
+    };
+
+    let (start, end) = match (start.parse::<u64>(), end.parse::<u64>()) {
+        // bytes=100-200
+        (Ok(start), Ok(end)) if start <= end => (start, end.saturating_add(1).min(len)),
+        // bytes=100-
+        (Ok(start), Err(_)) if end.is_empty() => (start, len),
+        // bytes=-100, last 100 bytes
+        (Err(_), Ok(suffix)) if start.is_empty() => {
+            if suffix == 0 {
+                return ByteRange::Unsatisfiable;
+            }
+
+            (len.saturating_sub(suffix), len)
+        }
+        _ => return ByteRange::Whole,
+    };
+
+    if start >= len {
+        return ByteRange::Unsatisfiable;
+    }
+
+    ByteRange::Partial(start, end)
+}
+
+/// `If-Range` allows range only if file
+/// is still the same as client has
+fn is_range_fresh<T>(req: &Request<T>, etag: &str, last_modified: Option<u64>) -> bool {
+    let Some(value) = req.headers().get(IF_RANGE) else {
+        return true;
+    };
+
+    let Ok(value) = value.to_str() else {
+        return false;
+    };
+
+    if value.starts_with('"') {
+        return value == etag;
+    }
+
+    matches!(
+        (parse_http_date(value), last_modified),
+        (Some(date), Some(modified)) if date == modified
+    )
+}
+
+/// Streams file in chunks of [`CHUNK_SIZE`]
+/// instead of loading it into memory
+struct FileBody {
+    file: File,
+    remaining: u64,
+    buff: Vec<u8>,
+}
+
+impl hyper::body::Body for FileBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, io::Error>>> {
+        let this = self.get_mut();
+
+        if this.remaining == 0 {
+            return Poll::Ready(None);
+        }
+
+        let len = this.remaining.min(CHUNK_SIZE as u64) as usize;
+        this.buff.resize(len, 0);
+
+        match Pin::new(&mut this.file).poll_read(cx, &mut this.buff) {
+            Poll::Ready(Ok(0)) => Poll::Ready(Some(Err(ErrorKind::UnexpectedEof.into()))),
+            Poll::Ready(Ok(n)) => {
+                this.remaining -= n as u64;
+                let chunk = Bytes::copy_from_slice(&this.buff[..n]);
+
+                Poll::Ready(Some(Ok(Frame::data(chunk))))
+            }
+            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.remaining == 0
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::with_exact(self.remaining)
+    }
+}
+
+/// Resolves percent-encoded `request` path relative to the `root`
+///
+/// Returns `None` if file doesn't exist or
+/// is located outside of the `root`
+pub fn resolve(root: &Path, request: &str) -> Option<PathBuf> {
+    let request = percent_decode(request)?;
+
+    if request.contains('\0') {
+        return None;
+    }
+
+    let root = root.canonicalize().ok()?;
+
+    // `join` replaces root with absolute paths
+    let path = root.join(&request);
+
+    // Symlinks and `..` are resolved here, so checking
+    // prefix afterwards is enough to reject traversal
+    let path = path.canonicalize().ok()?;
+
+    (path.starts_with(&root) && path.is_file()).then_some(path)
+}
+
+/// Whether `name` is a single file or folder name, so joining
+/// it to a path always results in a direct child of that path
+pub fn is_plain_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+
+    matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    ) && !name.contains(['/', '\\'])
+}
+
+pub fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut iter = value.bytes();
+
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let hex = [iter.next()?, iter.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+pub fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|v| v.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "avi" => "video/x-msvideo",
+        "flv" => "video/x-flv",
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "osu" | "osb" | "ini" | "txt" => "text/plain; charset=utf-8",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        _ => "application/octet-stream",
+    }
+}
+
+fn etag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos())
+}
+
+/// `If-None-Match` takes precedence over `If-Modified-Since`
+fn is_not_modified<T>(req: &Request<T>, etag: &str, modified: Option<u64>) -> bool {
+    if let Some(value) = req.headers().get(IF_NONE_MATCH) {
+        let Ok(value) = value.to_str() else {
+            return false;
+        };
+
+        return value
+            .split(',')
+            .map(|v| v.trim().trim_start_matches("W/"))
+            .any(|v| v == "*" || v == etag);
+    }
+
+    let since = req
+        .headers()
+        .get(IF_MODIFIED_SINCE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(parse_http_date);
+
+    matches!((since, modified), (Some(since), Some(modified)) if modified <= since)
+}
+
+/// Formats unix timestamp as IMF-fixdate,
+/// e.g. `Sun, 06 Nov 1994 08:49:37 GMT`
+pub fn http_date(secs: u64) -> String {
+    let days = secs / 86400;
+    let time = secs % 86400;
+
+    let (year, month, day) = civil_from_days(days as i64);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+        // 1970-01-01 was a Thursday
+        WEEKDAYS[((days + 4) % 7) as usize],
+        day,
+        MONTHS[month as usize - 1],
+        year,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+    )
+}
+
+/// Parses IMF-fixdate into unix timestamp,
+/// obsolete formats are not supported
+pub fn parse_http_date(value: &str) -> Option<u64> {
+    let mut parts = value.split_whitespace().skip(1);
+
+    let day: u32 = parts.next()?.parse().ok()?;
+    let month = parts.next()?;
+    let month = MONTHS.iter().position(|v| *v == month)? as u32 + 1;
+    let year: i64 = parts.next()?.parse().ok()?;
+
+    let mut time = parts.next()?.split(':').map(|v| v.parse::<u64>().ok());
+    let (hours, minutes, seconds) = (time.next()??, time.next()??, time.next()??);
+
+    if parts.next()? != "GMT" {
+        return None;
+    }
+
+    let days = u64::try_from(days_from_civil(year, month, day)).ok()?;
+
+    Some(days * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+// Both conversions are taken from
+// http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_http_date() {
+        assert_eq!(http_date(784111777), "Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(http_date(0), "Thu, 01 Jan 1970 00:00:00 GMT");
+        assert_eq!(
+            parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"),
+            Some(784111777)
+        );
+        assert_eq!(parse_http_date(&http_date(1709251199)), Some(1709251199));
+        assert_eq!(parse_http_date("Sunday, 06-Nov-94 08:49:37 GMT"), None);
+    }
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(None, 1000), ByteRange::Whole);
+        assert_eq!(
+            parse_range(Some("bytes=0-99"), 1000),
+            ByteRange::Partial(0, 100)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=900-"), 1000),
+            ByteRange::Partial(900, 1000)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-100"), 1000),
+            ByteRange::Partial(900, 1000)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-5000"), 1000),
+            ByteRange::Partial(0, 1000)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=500-5000"), 1000),
+            ByteRange::Partial(500, 1000)
+        );
+        assert_eq!(
+            parse_range(Some("bytes=1000-"), 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range(Some("bytes=0-1,5-9"), 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(
+            parse_range(Some("bytes=-0"), 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range(Some("bytes=9-1"), 1000), ByteRange::Whole);
+        assert_eq!(parse_range(Some("items=0-1"), 1000), ByteRange::Whole);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let root = std::env::temp_dir().join(format!("rosu-files-{}", std::process::id()));
+        let songs = root.join("Songs");
+        std::fs::create_dir_all(songs.join("123 Artist - Title")).unwrap();
+        std::fs::write(songs.join("123 Artist - Title/bg 1.jpg"), b"bg").unwrap();
+        std::fs::write(root.join("osu!.cfg"), b"secret").unwrap();
+
+        let bg = songs
+            .join("123 Artist - Title/bg 1.jpg")
+            .canonicalize()
+            .unwrap();
+
+        assert_eq!(
+            resolve(&songs, "123%20Artist%20-%20Title/bg%201.jpg"),
+            Some(bg.clone())
+        );
+        assert_eq!(resolve(&songs, bg.to_str().unwrap()), Some(bg));
+
+        assert_eq!(resolve(&songs, "../osu!.cfg"), None);
+        assert_eq!(resolve(&songs, "123 Artist - Title/../../osu!.cfg"), None);
+        assert_eq!(resolve(&songs, "%2e%2e/osu!.cfg"), None);
+        assert_eq!(
+            resolve(&songs, root.join("osu!.cfg").to_str().unwrap()),
+            None
+        );
+        assert_eq!(resolve(&songs, "123 Artist - Title"), None);
+        assert_eq!(resolve(&songs, "missing.jpg"), None);
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_is_plain_name() {
+        assert!(is_plain_name("lain's skin"));
+        assert!(is_plain_name(".hidden"));
+
+        for name in ["", ".", "..", "a/b", "a\\b", "skin/", "/skin", "C:\\skin"] {
+            assert!(!is_plain_name(name), "{name}");
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use eyre::{Report, Result};
+use serde_json::{Map, Value};
+
+/// Parses subscription message sent by the client,
+/// which is a JSON array of JSON pointers (RFC 6901)
+///
+/// Example: `["/gameplay/combo", "/beatmap/title"]`
+pub fn parse_subscription(text: &str) -> Result<Vec<String>> {
+    let pointers: Vec<String> = serde_json::from_str(text)?;
+
+    if let Some(pointer) = pointers
+        .iter()
+        .find(|v| !v.is_empty() && !v.starts_with('/'))
+    {
+        return Err(Report::msg(format!("Invalid JSON pointer: {pointer}")));
+    }
+
+    Ok(pointers)
+}
+
+/// Builds object that contains only subtrees pointed by `pointers`,
+/// keeping their original location. Missing subtrees are skipped
+pub fn select(value: &Value, pointers: &[String]) -> Value {
+    let mut selected = Value::Object(Map::new());
+
+    'pointers: for pointer in pointers {
+        let Some(subtree) = value.pointer(pointer) else {
+            continue;
+        };
+
+        // Empty pointer refers to the whole document
+        if pointer.is_empty() {
+            return value.clone();
+        }
+
+        let mut target = &mut selected;
+
+        for token in pointer[1..].split('/') {
+            let token = token.replace("~1", "/").replace("~0", "~");
+
+            // Subtree is already included by one of the parents
+            let Value::Object(map) = target else {
+                continue 'pointers;
+            };
+
+            target = map
+                .entry(token)
+                .or_insert_with(|| Value::Object(Map::new()));
+        }
+
+        *target = subtree.clone();
+    }
+
+    selected
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_select() {
+        let value = json!({
+            "gameplay": { "combo": 5, "hit_errors": [1, 2], "username": "Vaxei" },
+            "beatmap": { "title": "Another Me", "artist": "Neuromancer" },
+            "keyoverlay": { "k1_pressed": true, "k1_count": 10 },
+            "a/b": 1,
+        });
+
+        let pointers = parse_subscription(
+            r#"["/gameplay/combo", "/beatmap/title", "/keyoverlay", "/a~1b", "/missing"]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            select(&value, &pointers),
+            json!({
+                "gameplay": { "combo": 5 },
+                "beatmap": { "title": "Another Me" },
+                "keyoverlay": { "k1_pressed": true, "k1_count": 10 },
+                "a/b": 1,
+            })
+        );
+
+        assert_eq!(select(&value, &["".to_owned()]), value);
+
+        let pointers = parse_subscription(r#"["/gameplay/hit_errors", "/gameplay/hit_errors/0"]"#);
+        assert_eq!(
+            select(&value, &pointers.unwrap()),
+            json!({ "gameplay": { "hit_errors": [1, 2] } })
+        );
+
+        assert!(parse_subscription(r#"["gameplay"]"#).is_err());
+        assert!(parse_subscription(r#"{"gameplay": 1}"#).is_err());
+    }
+}
@@ -1,132 +1,277 @@
+pub mod body;
+pub mod broadcast;
+pub mod counters;
+pub mod files;
+pub mod filter;
+pub mod patch;
 pub mod smol_hyper;
-
-use http_body_util::Full;
-
-use std::net::TcpListener;
+pub mod sse;
+
+use std::{
+    fmt::Write,
+    net::{SocketAddr, TcpListener},
+    path::Path,
+    sync::Arc,
+    time::{Duration, Instant, UNIX_EPOCH},
+};
 
 use crate::{
-    gosu_structs::GosuValues,
+    config::Config,
+    metrics::{self, Metrics},
+    skin::{self, SkinIni},
     structs::{Arm, Clients, OutputValues, WsClient, WsKind},
 };
 
-use self::smol_hyper::SmolIo;
-use smol::{Async, prelude::*};
+use self::{
+    body::Body,
+    broadcast::{
+        CLIENT_QUEUE_SIZE, EVENT_QUEUE_SIZE, Encoder, EventFrame, Format, Frame, FrameQueue,
+        Snapshot, broadcaster,
+    },
+    smol_hyper::SmolIo,
+};
+use smol::{Async, channel::Receiver, prelude::*};
 
 use async_tungstenite::{
     WebSocketStream,
-    tungstenite::{Message, handshake::derive_accept_key, protocol::Role},
+    tungstenite::{
+        Error as WsError, Message, Utf8Bytes, handshake::derive_accept_key, protocol::Role,
+    },
 };
 
 use eyre::Result;
 use hyper::{
     Request, Response, StatusCode,
     body::Bytes,
-    header::{CONNECTION, HeaderValue, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, UPGRADE},
+    header::{
+        CONNECTION, CONTENT_TYPE, HeaderValue, LOCATION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY,
+        SEC_WEBSOCKET_PROTOCOL, UPGRADE,
+    },
     server::conn::http1,
     service::service_fn,
+    upgrade::Upgraded,
 };
 
-pub async fn handle_clients(values: Arm<OutputValues>, clients: Clients) {
-    let _span = tracy_client::span!("handle clients");
+/// Owns client's websocket, sends queued frames
+/// and watches for incoming `Close` and subscription messages.
+/// Slow client only delays its own queue
+async fn client_task(
+    mut client: WebSocketStream<SmolIo<Upgraded>>,
+    mut encoder: Encoder,
+    frames: Receiver<Frame>,
+) {
+    enum Action {
+        Incoming(Option<Result<Message, WsError>>),
+        Frame(Option<Frame>),
+    }
 
-    let (serialized_rosu_values, serialized_gosu_values) = {
-        let values_lock = values.lock().unwrap();
+    loop {
+        let incoming = async { Action::Incoming(client.next().await) };
+        let frame = async { Action::Frame(frames.recv().await.ok()) };
+
+        match incoming.or(frame).await {
+            Action::Incoming(Some(Ok(Message::Close(_))))
+            | Action::Incoming(Some(Err(_)))
+            | Action::Incoming(None) => break,
+            Action::Incoming(Some(Ok(Message::Text(text)))) => {
+                match filter::parse_subscription(text.as_str()) {
+                    Ok(pointers) => encoder.subscribe(pointers),
+                    Err(e) => println!("Invalid subscription message: {}", e),
+                }
+            }
+            Action::Incoming(Some(Ok(_))) => continue,
+            Action::Frame(None) => break,
+            Action::Frame(Some(frame)) => {
+                let Some(message) = encoder.encode(frame) else {
+                    continue;
+                };
+
+                // When some sort of websocket's error happened
+                // Just close current websocket connection
+                // and try to keep going (also notify user)
+                // instead of panicking
+                if let Err(e) = client.send(message).await {
+                    println!("{:?}", e);
+
+                    // Ignoring result of `Close` message
+                    let _ = client.send(Message::Close(None)).await;
+
+                    break;
+                }
+            }
+        }
+    }
 
-        let values = &*values_lock;
+    // Lets broadcaster know that this client is gone
+    frames.close();
+}
 
-        let gosu_values: GosuValues = values.into();
+/// State shared between all connections
+#[derive(Clone)]
+struct Context {
+    clients: Clients,
+    values: Arm<OutputValues>,
 
-        (
-            serde_json::to_string(&values).unwrap(),
-            serde_json::to_string(&gosu_values).unwrap(),
-        )
-    };
+    /// Last published snapshot
+    latest: Arm<Option<Arc<Snapshot>>>,
 
-    let mut clients = clients.lock().unwrap();
-    clients.retain_mut(|websocket| {
-        smol::block_on(async {
-            let next_future = websocket.client.next();
+    /// Effective config, replaced on reload
+    config: Arm<Config>,
 
-            let msg_future = smol::future::poll_once(next_future);
+    metrics: Arc<Metrics>,
 
-            let msg = match msg_future.await {
-                Some(Some(Ok(v))) => Some(v),
-                Some(Some(Err(_))) => return false,
-                Some(None) | None => None,
-            };
+    /// When server was started, used for uptime
+    started: Instant,
+}
 
-            if let Some(Message::Close(_)) = msg {
-                return false;
-            };
+pub fn server_thread(
+    values: Arm<OutputValues>,
+    snapshots: Receiver<Arc<Snapshot>>,
+    events: Receiver<EventFrame>,
+    precise: Receiver<Utf8Bytes>,
+    metrics: Arc<Metrics>,
+    config: Arm<Config>,
+) {
+    let (addresses, retry_interval) = {
+        let config = config.lock().unwrap();
+        (config.listen_addresses(), config.error_interval())
+    };
 
-            let res = if websocket.kind == WsKind::Gosu {
-                // If gosu values is not serialized yet, do this now
-                websocket
-                    .client
-                    .send(Message::Text(serialized_gosu_values.clone().into()))
-                    .await
-            } else {
-                websocket
-                    .client
-                    .send(Message::Text(serialized_rosu_values.clone().into()))
-                    .await
-            };
+    let ctx = Context {
+        clients: Clients::default(),
+        values,
+        latest: Arm::default(),
+        config,
+        metrics,
+        started: Instant::now(),
+    };
+
+    smol::block_on(async {
+        smol::spawn(broadcaster(
+            snapshots,
+            events,
+            precise,
+            ctx.clients.clone(),
+            ctx.latest.clone(),
+        ))
+        .detach();
+
+        let listeners: Vec<_> = addresses
+            .into_iter()
+            .map(|addr| smol::spawn(listen(addr, retry_interval, ctx.clone())))
+            .collect();
+
+        for listener in listeners {
+            listener.await;
+        }
+    })
+}
 
-            // When some sort of websocket's error happened
-            // Just close current websocket connection
-            // and try to keep going (also notify user)
-            // instead of panicking
-            if let Err(e) = res {
-                println!("{:?}", e);
+/// Binds to the provided address and accepts connections
+/// forever. If address is not available (port is taken, interface
+/// is down, etc.) keeps retrying instead of bringing server down
+async fn listen(addr: SocketAddr, retry_interval: Duration, ctx: Context) {
+    let listener = loop {
+        match Async::<TcpListener>::bind(addr) {
+            Ok(v) => break v,
+            Err(e) => {
+                println!(
+                    "Failed to bind {addr}: {e}, retrying in {}s",
+                    retry_interval.as_secs()
+                );
+                smol::Timer::after(retry_interval).await;
+            }
+        }
+    };
 
-                // Ignoring result of `Close` message
-                let _ = websocket.client.send(Message::Close(None)).await;
+    println!("Listening on http://{addr}");
 
-                return false;
-            };
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                println!("Failed to accept connection on {addr}: {e}");
+                continue;
+            }
+        };
 
-            true
+        let io = SmolIo::new(stream);
+
+        let ctx = ctx.clone();
+        let service = service_fn(move |req| serve(ctx.clone(), req));
+
+        smol::spawn(async {
+            if let Err(err) = http1::Builder::new()
+                .serve_connection(io, service)
+                .with_upgrades()
+                .await
+            {
+                println!("Error serving connection: {:?}", err);
+            }
         })
-    });
+        .detach();
+    }
 }
 
-pub fn server_thread(ctx_clients: Clients, values: Arm<OutputValues>) {
-    smol::block_on(async {
-        let tcp = TcpListener::bind("127.0.0.1:24050").unwrap();
-        let listener = Async::new(tcp).unwrap();
-
-        loop {
-            let (stream, _) = listener.accept().await.unwrap();
-
-            let io = SmolIo::new(stream);
-
-            let ctx_clients = ctx_clients.clone();
-            let ctx_values = values.clone();
-            let service = service_fn(move |req| {
-                let ctx_clients = ctx_clients.clone();
-                let ctx_values = ctx_values.clone();
-                serve(ctx_clients, ctx_values, req)
-            });
-
-            smol::spawn(async {
-                if let Err(err) = http1::Builder::new()
-                    .serve_connection(io, service)
-                    .with_upgrades()
-                    .await
-                {
-                    println!("Error serving connection: {:?}", err);
-                }
-            })
-            .detach();
+/// Client options passed through query string,
+/// e.g. `/rws?diff=true&format=msgpack`
+#[derive(Default)]
+struct WsOptions {
+    /// Send JSON Merge Patches instead of full snapshots
+    diff: bool,
+
+    format: Format,
+
+    /// Format negotiated through `Sec-WebSocket-Protocol`,
+    /// has to be confirmed in the response
+    subprotocol: Option<Format>,
+}
+
+impl WsOptions {
+    /// Formats that are not in `formats` are ignored
+    fn from_request(req: &Request<hyper::body::Incoming>, formats: &[Format]) -> Self {
+        let parse = |name: &str| Format::from_name(name).filter(|v| formats.contains(v));
+
+        let mut options = Self::default();
+
+        for pair in req.uri().query().unwrap_or_default().split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, "true"));
+
+            match key {
+                "diff" => options.diff = matches!(value, "true" | "1"),
+                "format" => options.format = parse(value).unwrap_or_default(),
+                _ => (),
+            }
         }
-    })
+
+        // Picking the first protocol we know about, query
+        // parameter has priority over it
+        options.subprotocol = req
+            .headers()
+            .get_all(SEC_WEBSOCKET_PROTOCOL)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .find_map(|v| parse(v.trim()));
+
+        if let Some(format) = options.subprotocol
+            && options.format == Format::Json
+        {
+            options.format = format;
+        }
+
+        options
+    }
 }
 
 async fn serve_ws(
     clients: Clients,
     mut req: Request<hyper::body::Incoming>,
     kind: WsKind,
-) -> Result<Response<Full<Bytes>>> {
+    formats: &[Format],
+) -> Result<Response<Body>> {
+    let options = WsOptions::from_request(&req, formats);
+
     let headers = req.headers();
     let key = headers.get(SEC_WEBSOCKET_KEY);
     let derived = key.map(|k| derive_accept_key(k.as_bytes()));
@@ -139,15 +284,26 @@ async fn serve_ws(
 
         let client = WebSocketStream::from_raw_socket(upgraded, Role::Server, None).await;
 
-        let ws_client = WsClient { client, kind };
+        let queue = match kind {
+            WsKind::Events => FrameQueue::new(EVENT_QUEUE_SIZE),
+            WsKind::Gosu | WsKind::Rosu | WsKind::Precise => FrameQueue::new(CLIENT_QUEUE_SIZE),
+        };
+        let frames = queue.receiver();
+
+        clients.lock().unwrap().push(WsClient {
+            kind,
+            events: kind == WsKind::Events,
+            sse: false,
+            queue,
+        });
 
-        let mut clients = clients.lock().unwrap();
+        let encoder = Encoder::new(kind, options.format, options.diff);
 
-        clients.push(ws_client);
+        client_task(client, encoder, frames).await;
     })
     .detach();
 
-    let mut res = Response::new(Full::new(Bytes::default()));
+    let mut res = Response::new(body::empty());
 
     *res.status_mut() = StatusCode::SWITCHING_PROTOCOLS;
     *res.version_mut() = ver;
@@ -158,6 +314,13 @@ async fn serve_ws(
     res.headers_mut()
         .append(UPGRADE, HeaderValue::from_static("websocket"));
 
+    if let Some(format) = options.subprotocol {
+        res.headers_mut().append(
+            SEC_WEBSOCKET_PROTOCOL,
+            HeaderValue::from_static(format.name()),
+        );
+    }
+
     res.headers_mut().append(
         SEC_WEBSOCKET_ACCEPT,
         derived.unwrap().parse().unwrap(), //TODO remove unwraps
@@ -166,46 +329,259 @@ async fn serve_ws(
     Ok(res)
 }
 
-async fn serve_http(
-    values: Arm<OutputValues>,
-    req: Request<hyper::body::Incoming>,
-) -> Result<Response<Full<Bytes>>> {
-    let mut path = req.uri().path().splitn(3, '/').skip(1);
+async fn serve_http(ctx: Context, req: Request<hyper::body::Incoming>) -> Result<Response<Body>> {
+    let path = req.uri().path();
 
-    let songs_path = match path.next() {
-        Some(v) => v,
-        None => return Ok(Response::builder().status(404).body(Full::default())?),
-    };
+    if let Some(request) = path.strip_prefix("/Songs/") {
+        let osu_path = ctx.values.lock().unwrap().osu_path.clone();
 
-    if songs_path.starts_with("Songs") {
-        let background_path = {
-            let values = values.lock().unwrap();
+        // Otherwise `Songs` relative to the working directory is served
+        if osu_path.as_os_str().is_empty() {
+            return Ok(Response::builder()
+                .status(StatusCode::SERVICE_UNAVAILABLE)
+                .body(body::empty())?);
+        }
+
+        return files::serve_file(&osu_path.join("Songs"), request, &req).await;
+    }
 
-            values.beatmap.paths.background_path_full.clone()
+    if path.starts_with("/skin/") || path == "/skin.json" {
+        let (skin_name, skin_dir, attached) = {
+            let values = ctx.values.lock().unwrap();
+            (
+                values.skin.clone(),
+                values.osu_path.join("Skins").join(&values.skin),
+                !values.osu_path.as_os_str().is_empty(),
+            )
         };
 
-        if !background_path.exists() {
-            return Ok(Response::builder().status(400).body(Full::default())?);
+        // Otherwise `Skins` folder or anything above is exposed,
+        // osu! folder is not known before attaching to osu!
+        if !attached || !files::is_plain_name(&skin_name) || !skin_dir.is_dir() {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(body::empty())?);
         }
 
-        let bytes = smol::fs::read(background_path).await?;
+        return serve_skin(&skin_name, &skin_dir, req).await;
+    }
 
-        Ok(Response::new(Full::new(Bytes::copy_from_slice(
-            bytes.as_slice(),
-        ))))
-    } else {
-        Ok(Response::builder().status(400).body(Full::default())?)
+    let static_dir = ctx.config.lock().unwrap().static_dir();
+
+    if path == "/counters" || path == "/counters/" {
+        let counters = smol::unblock(move || counters::list(&static_dir)).await;
+
+        return Ok(Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(body::full(serde_json::to_vec(&counters)?))?);
     }
+
+    // Counters are served both under `/counters/<name>/` and `/<name>/`
+    let request = path
+        .strip_prefix("/counters")
+        .filter(|v| v.starts_with('/'))
+        .unwrap_or(path);
+
+    // Relative links inside of the counter are not
+    // going to work without trailing slash
+    let name = files::percent_decode(&request[1..]).unwrap_or_default();
+    if !name.is_empty()
+        && !name.contains(['/', '\\'])
+        && !name.starts_with('.')
+        && static_dir.join(&name).is_dir()
+    {
+        return Ok(Response::builder()
+            .status(StatusCode::MOVED_PERMANENTLY)
+            .header(LOCATION, format!("{path}/"))
+            .body(body::empty())?);
+    }
+
+    let request = if request.ends_with('/') {
+        format!("{request}index.html")
+    } else {
+        request.to_owned()
+    };
+
+    files::serve_file(&static_dir, request.trim_start_matches('/'), &req).await
 }
 
-async fn serve(
-    clients: Clients,
-    values: Arm<OutputValues>,
+/// Serves `/skin/<file>` from the current skin
+/// folder and `/skin.json` from its `skin.ini`
+async fn serve_skin(
+    skin_name: &str,
+    skin_dir: &Path,
     req: Request<hyper::body::Incoming>,
-) -> Result<Response<Full<Bytes>>> {
+) -> Result<Response<Body>> {
+    if let Some(request) = req.uri().path().strip_prefix("/skin/") {
+        let request = match files::resolve(skin_dir, request) {
+            Some(_) => request.to_owned(),
+            None => skin::fallback_name(request).unwrap_or_else(|| request.to_owned()),
+        };
+
+        return files::serve_file(skin_dir, &request, &req).await;
+    }
+
+    // Skins without `skin.ini` are using defaults
+    let text = smol::fs::read(skin_dir.join("skin.ini"))
+        .await
+        .unwrap_or_default();
+
+    let mut skin = SkinIni::parse(&String::from_utf8_lossy(&text));
+
+    if skin.name.is_empty() {
+        skin_name.clone_into(&mut skin.name);
+    }
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(body::full(serde_json::to_vec(&skin)?))?)
+}
+
+/// Serves last published values, anything after `/json`
+/// (gosu) or `/json/rosu` is treated as JSON pointer to
+/// the part of values, e.g. `/json/rosu/gameplay/combo`
+fn serve_json(latest: &Arm<Option<Arc<Snapshot>>>, path: &str) -> Result<Response<Body>> {
+    let (kind, pointer) = match path.strip_prefix("/json/rosu") {
+        Some(v) if v.is_empty() || v.starts_with('/') => (WsKind::Rosu, v),
+        _ => (WsKind::Gosu, path.strip_prefix("/json").unwrap_or_default()),
+    };
+
+    let Some(snapshot) = latest.lock().unwrap().clone() else {
+        return Ok(Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(body::empty())?);
+    };
+
+    let json: Bytes = match (kind, pointer) {
+        (WsKind::Rosu, "") => snapshot.rosu.clone().into(),
+        (_, "") => snapshot.gosu.clone().into(),
+        _ => {
+            let value = snapshot.value(kind).and_then(|v| v.pointer(pointer));
+
+            let Some(value) = value else {
+                return Ok(Response::builder()
+                    .status(StatusCode::NOT_FOUND)
+                    .body(body::empty())?);
+            };
+
+            serde_json::to_vec(value)?.into()
+        }
+    };
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(body::full(json))?)
+}
+
+/// Reader metrics, connected clients and gameplay
+/// gauges in Prometheus text format
+fn serve_metrics(ctx: &Context) -> Result<Response<Body>> {
+    let mut out = String::new();
+
+    ctx.metrics.render(&mut out);
+
+    // By transport, then by kind
+    let mut clients = [[0; 4]; 2];
+    for client in ctx.clients.lock().unwrap().iter() {
+        if !client.queue.is_closed() {
+            clients[client.sse as usize][client.kind as usize] += 1;
+        }
+    }
+
+    metrics::header(
+        &mut out,
+        "rosu_clients",
+        "gauge",
+        "Connected clients by endpoint kind and transport",
+    );
+    for (transport, counts) in ["websocket", "sse"].iter().zip(clients) {
+        for (kind, count) in ["gosu", "rosu", "events", "precise"].iter().zip(counts) {
+            let _ = writeln!(
+                out,
+                "rosu_clients{{kind=\"{kind}\",transport=\"{transport}\"}} {count}"
+            );
+        }
+    }
+
+    let gauges = {
+        let values = ctx.values.lock().unwrap();
+
+        [
+            ("rosu_current_pp", "Current pp", values.current_pp),
+            ("rosu_combo", "Current combo", values.gameplay.combo as f64),
+            (
+                "rosu_unstable_rate",
+                "Current unstable rate",
+                values.gameplay.unstable_rate,
+            ),
+            ("rosu_hp", "Current HP", values.gameplay.current_hp),
+        ]
+    };
+
+    for (name, help, value) in gauges {
+        metrics::header(&mut out, name, "gauge", help);
+        let _ = writeln!(out, "{name} {value}");
+    }
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(body::full(out))?)
+}
+
+/// Reader diagnostics: whether osu! was found,
+/// signatures were resolved and ticks are going
+fn serve_status(ctx: &Context) -> Result<Response<Body>> {
+    let reader = ctx.metrics.status.lock().unwrap().clone();
+
+    let last_tick = ctx
+        .metrics
+        .last_tick()
+        .and_then(|v| v.duration_since(UNIX_EPOCH).ok())
+        .map(|v| v.as_millis() as u64);
+
+    let signatures: serde_json::Map<String, serde_json::Value> = reader
+        .signatures
+        .iter()
+        .map(|v| (v.name.to_owned(), v.address.into()))
+        .collect();
+
+    let status = serde_json::json!({
+        "version": env!("CARGO_PKG_VERSION"),
+        "uptime_secs": ctx.started.elapsed().as_secs(),
+        "pid": reader.pid,
+        "osu_path_source": reader.osu_path_source,
+        "signatures": signatures,
+        "last_tick_ms": last_tick,
+        "last_error": reader.last_error,
+    });
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .body(body::full(serde_json::to_vec(&status)?))?)
+}
+
+async fn serve(ctx: Context, req: Request<hyper::body::Incoming>) -> Result<Response<Body>> {
+    let (endpoints, formats) = {
+        let config = ctx.config.lock().unwrap();
+        (config.endpoints, config.formats.clone())
+    };
+
+    if !endpoints.is_enabled(req.uri().path()) {
+        return Ok(Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(body::empty())?);
+    }
+
     match req.uri().path() {
-        "/ws" => serve_ws(clients, req, WsKind::Gosu).await,
-        "/rws" => serve_ws(clients, req, WsKind::Rosu).await,
-        _ => serve_http(values, req).await,
+        "/ws" => serve_ws(ctx.clients, req, WsKind::Gosu, &formats).await,
+        "/rws" => serve_ws(ctx.clients, req, WsKind::Rosu, &formats).await,
+        "/events" => serve_ws(ctx.clients, req, WsKind::Events, &formats).await,
+        "/precise" => serve_ws(ctx.clients, req, WsKind::Precise, &formats).await,
+        "/sse" => sse::serve_sse(ctx.clients, WsKind::Rosu),
+        "/sse/gosu" => sse::serve_sse(ctx.clients, WsKind::Gosu),
+        "/metrics" => serve_metrics(&ctx),
+        "/status" => serve_status(&ctx),
+        path if path == "/json" || path.starts_with("/json/") => serve_json(&ctx.latest, path),
+        _ => serve_http(ctx, req).await,
     }
 }
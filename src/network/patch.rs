@@ -0,0 +1,99 @@
+use serde_json::{Map, Value};
+
+/// Builds JSON Merge Patch (RFC 7386) that turns `old` into `new`
+///
+/// Returns `None` when values are equal. Arrays are
+/// replaced as a whole, removed keys are set to `null`.
+/// Patch can't tell removed keys from the ones that became
+/// `null`, after applying it both of them are removed
+pub fn merge_patch(old: &Value, new: &Value) -> Option<Value> {
+    let (Value::Object(old), Value::Object(new)) = (old, new) else {
+        return (old != new).then(|| new.clone());
+    };
+
+    let mut patch = Map::new();
+
+    for (key, new_value) in new {
+        match old.get(key) {
+            Some(old_value) => {
+                if let Some(v) = merge_patch(old_value, new_value) {
+                    patch.insert(key.clone(), v);
+                }
+            }
+            None => {
+                patch.insert(key.clone(), new_value.clone());
+            }
+        }
+    }
+
+    for key in old.keys() {
+        if !new.contains_key(key) {
+            patch.insert(key.clone(), Value::Null);
+        }
+    }
+
+    (!patch.is_empty()).then_some(Value::Object(patch))
+}
+
+/// Applies JSON Merge Patch (RFC 7386) to `target`
+pub fn apply_merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                apply_merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_merge_patch() {
+        let old = json!({
+            "state": 2,
+            "gameplay": { "combo": 5, "hit_errors": [1, 2], "username": "Vaxei" },
+            "skin": "lain's skin",
+        });
+
+        assert_eq!(merge_patch(&old, &old), None);
+
+        let new = json!({
+            "state": 2,
+            "gameplay": { "combo": 6, "hit_errors": [1, 2, 3], "username": "Vaxei" },
+        });
+
+        assert_eq!(
+            merge_patch(&old, &new),
+            Some(json!({
+                "gameplay": { "combo": 6, "hit_errors": [1, 2, 3] },
+                "skin": null,
+            }))
+        );
+
+        assert_eq!(merge_patch(&json!(1), &json!("1")), Some(json!("1")));
+
+        let mut patched = old.clone();
+        apply_merge_patch(&mut patched, &merge_patch(&old, &new).unwrap());
+        assert_eq!(patched, new);
+
+        let mut patched = json!(null);
+        apply_merge_patch(&mut patched, &new);
+        assert_eq!(patched, new);
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use eyre::Result;
+use http_body_util::BodyExt;
+use hyper::{
+    Response,
+    body::{Bytes, Frame as BodyFrame},
+    header::{CACHE_CONTROL, CONTENT_TYPE},
+};
+use smol::{channel::Receiver, prelude::*};
+
+use crate::structs::{Clients, WsClient, WsKind};
+
+use super::{
+    body::Body,
+    broadcast::{EVENT_QUEUE_SIZE, Frame, FrameQueue, HitErrorsCursor},
+};
+
+/// Streams values as unnamed `message` events and
+/// game transitions as events named after them,
+/// e.g. `event: play_started`
+pub fn serve_sse(clients: Clients, kind: WsKind) -> Result<Response<Body>> {
+    // Events are not dropped because of snapshots flood
+    let queue = FrameQueue::new(EVENT_QUEUE_SIZE);
+    let frames = queue.receiver();
+
+    clients.lock().unwrap().push(WsClient {
+        kind,
+        events: true,
+        sse: true,
+        queue,
+    });
+
+    let body = SseBody {
+        kind,
+        frames,
+        hit_errors: HitErrorsCursor::default(),
+    };
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(body.boxed())?)
+}
+
+struct SseBody {
+    kind: WsKind,
+    frames: Receiver<Frame>,
+    hit_errors: HitErrorsCursor,
+}
+
+impl SseBody {
+    fn encode(&mut self, frame: Frame) -> Option<Bytes> {
+        let text = match frame {
+            Frame::Snapshot(snapshot) if self.kind == WsKind::Rosu => {
+                match self.hit_errors.resync(&snapshot) {
+                    Some(value) => format!("data: {}\n\n", value),
+                    None => format!("data: {}\n\n", snapshot.json(self.kind)?),
+                }
+            }
+            Frame::Snapshot(snapshot) => format!("data: {}\n\n", snapshot.json(self.kind)?),
+            Frame::Event(event) => format!("event: {}\ndata: {}\n\n", event.name, event.json),
+            Frame::Precise(_) => return None,
+        };
+
+        Some(text.into())
+    }
+}
+
+impl hyper::body::Body for SseBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<BodyFrame<Bytes>, io::Error>>> {
+        loop {
+            let frame = match self.frames.poll_next(cx) {
+                Poll::Ready(Some(frame)) => frame,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            if let Some(bytes) = self.encode(frame) {
+                return Poll::Ready(Some(Ok(BodyFrame::data(bytes))));
+            }
+        }
+    }
+}
+
+impl Drop for SseBody {
+    /// Lets broadcaster know that this client is gone
+    fn drop(&mut self) {
+        self.frames.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::network::broadcast::{EventFrame, Snapshot};
+
+    #[test]
+    fn test_sse_encode() {
+        let queue = FrameQueue::default();
+        let mut body = SseBody {
+            kind: WsKind::Gosu,
+            frames: queue.receiver(),
+            hit_errors: HitErrorsCursor::default(),
+        };
+
+        let snapshot = Snapshot::from_json(r#"{"state":2}"#.into(), r#"{"menu":{}}"#.into());
+        assert_eq!(
+            body.encode(Frame::Snapshot(Arc::new(snapshot))).unwrap(),
+            "data: {\"menu\":{}}\n\n"
+        );
+
+        let event = EventFrame {
+            name: "play_started",
+            json: r#"{"event":"play_started","data":{}}"#.into(),
+        };
+        assert_eq!(
+            body.encode(Frame::Event(event)).unwrap(),
+            "event: play_started\ndata: {\"event\":\"play_started\",\"data\":{}}\n\n"
+        );
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    collections::VecDeque,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use eyre::{Report, Result};
+use rosu_mem::{
+    error::ProcessError,
+    process::{Process, ProcessTraits},
+};
+
+use crate::{
+    events::GameEvent,
+    metrics::{Metrics, OsuPathSource},
+    reading_loop::{process_precise, process_reading_loop},
+    structs::{
+        Arm, BeatmapValues, GameState, GameplayValues, InnerValues, OutputValues,
+        PreciseValues, RawValues, Section, State, StaticAddresses,
+    },
+};
+
+// Workaround for new winello umu-run stuff
+static EXCLUDE_WORDS: [&str; 2] = ["umu-run", "waitforexitandrun"];
+
+#[derive(Debug)]
+pub enum ReaderEvent {
+    /// osu! process was found and static addresses were read
+    Attached {
+        pid: u32,
+        osu_path: PathBuf,
+        osu_path_source: OsuPathSource,
+    },
+
+    /// osu! process is gone, reader is going to look for it again
+    Detached,
+
+    /// Reading loop tick is finished and values are up to date
+    Tick(RawValues),
+
+    /// Precise values were read between ticks,
+    /// only happens when precise interval is set
+    Precise(PreciseValues),
+
+    /// Game transition detected during the last tick, always
+    /// follows the first finished `Tick` after it was detected
+    Game(GameEvent),
+
+    /// Something went wrong, reader is going to try
+    /// again after `error_interval`
+    Error(Report),
+
+    /// Part of the tick couldn't be read and was skipped, the rest
+    /// of the values are still up to date. Reported once per
+    /// failure streak, until the section is read again
+    Skipped { section: Section, error: Report },
+}
+
+/// Intervals that replace the default one in some game states,
+/// e.g. to update gameplay counters more often
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct StateIntervals {
+    /// `Playing`
+    pub playing: Option<Duration>,
+
+    /// `SongSelect`, `EditorSongSelect` and multiplayer lobbies
+    pub song_select: Option<Duration>,
+
+    /// `PreSongSelect` (main menu)
+    pub idle: Option<Duration>,
+}
+
+impl StateIntervals {
+    pub fn get(&self, state: GameState, default: Duration) -> Duration {
+        let interval = match state {
+            GameState::Playing => self.playing,
+            GameState::SongSelect
+            | GameState::EditorSongSelect
+            | GameState::MultiplayerLobbySelect
+            | GameState::MultiplayerLobby => self.song_select,
+            GameState::PreSongSelect => self.idle,
+            _ => None,
+        };
+
+        interval.unwrap_or(default)
+    }
+}
+
+/// Owns osu! process, its static addresses and reading loop state
+///
+/// Works as an endless iterator of [`ReaderEvent`], waiting
+/// `interval` between ticks and `error_interval` after errors.
+/// `Err` is returned only when reader can't continue at all
+/// (e.g. osu! folder can't be found)
+pub struct Reader {
+    state: State,
+    process: Option<Process>,
+
+    /// Overwrites auto-detected osu! folder path
+    osu_path: Option<PathBuf>,
+
+    interval: Duration,
+    error_interval: Duration,
+    state_intervals: StateIntervals,
+
+    /// Interval of reading precise values between
+    /// ticks, they are not read when `None`
+    precise_interval: Option<Duration>,
+    precise: PreciseValues,
+
+    /// Whether the last step was a successful tick in `Playing`
+    /// state, precise values are only read after them
+    ticking: bool,
+
+    /// Delay before the next step
+    delay: Option<Duration>,
+
+    /// When the next step is going to be made
+    deadline: Option<Instant>,
+
+    /// Events that are yielded before making the next step
+    pending: VecDeque<ReaderEvent>,
+
+    metrics: Arc<Metrics>,
+}
+
+impl Reader {
+    pub fn new(osu_path: Option<PathBuf>, interval: Duration, error_interval: Duration) -> Self {
+        Self {
+            state: State {
+                addresses: StaticAddresses::default(),
+                values: Arc::new(Mutex::new(OutputValues::default())),
+                ivalues: InnerValues::default(),
+            },
+            process: None,
+            osu_path,
+            interval,
+            error_interval,
+            state_intervals: StateIntervals::default(),
+            precise_interval: None,
+            precise: PreciseValues::default(),
+            ticking: false,
+            delay: None,
+            deadline: None,
+            pending: VecDeque::new(),
+            metrics: Arc::default(),
+        }
+    }
+
+    /// Values shared with the reading loop, updated every tick
+    pub fn values(&self) -> Arm<OutputValues> {
+        self.state.values.clone()
+    }
+
+    /// Tick durations and errors, shared with the server
+    pub fn metrics(&self) -> Arc<Metrics> {
+        self.metrics.clone()
+    }
+
+    pub fn gameplay(&self) -> GameplayValues {
+        self.state.values.lock().unwrap().gameplay.clone()
+    }
+
+    pub fn beatmap(&self) -> BeatmapValues {
+        self.state.values.lock().unwrap().beatmap.clone()
+    }
+
+    /// Takes effect starting from the next step
+    pub fn set_intervals(&mut self, interval: Duration, error_interval: Duration) {
+        self.interval = interval;
+        self.error_interval = error_interval;
+    }
+
+    /// Takes effect starting from the next step
+    pub fn set_state_intervals(&mut self, state_intervals: StateIntervals) {
+        self.state_intervals = state_intervals;
+    }
+
+    /// Takes effect starting from the next step
+    pub fn set_precise_interval(&mut self, precise_interval: Option<Duration>) {
+        self.precise_interval = precise_interval;
+    }
+
+    pub fn is_attached(&self) -> bool {
+        self.process.is_some()
+    }
+
+    /// Makes a single step without any waiting: either tries to
+    /// attach to the osu! process or runs one reading loop tick
+    pub fn step(&mut self) -> Result<ReaderEvent> {
+        self.ticking = false;
+
+        let Some(ref p) = self.process else {
+            return self.attach();
+        };
+
+        let start = Instant::now();
+
+        match process_reading_loop(p, &mut self.state) {
+            Ok(()) => {
+                let interval = {
+                    let mut values = self.state.values.lock().unwrap();
+                    let interval = self.state_intervals.get(values.state, self.interval);
+
+                    values.polling_interval = interval.as_millis() as u64;
+                    self.ticking = values.state == GameState::Playing;
+
+                    interval
+                };
+
+                self.delay = Some(interval);
+
+                self.metrics.observe_tick(start.elapsed());
+                self.metrics
+                    .set_beatmap_parse_failures(self.state.ivalues.beatmap_parse_failures);
+
+                self.pending
+                    .extend(self.state.ivalues.events.drain(..).map(ReaderEvent::Game));
+
+                // Last error is the one of the last tick
+                self.metrics.clear_last_error();
+                self.report_skipped();
+
+                Ok(ReaderEvent::Tick(self.state.ivalues.raw))
+            }
+            Err(e) => {
+                self.delay = Some(self.error_interval);
+
+                // Events are detected only once, so they are kept
+                // and reported after the next finished tick
+                self.metrics.observe_error("tick", &e);
+                self.report_skipped();
+
+                if is_process_lost(&e) {
+                    self.state.ivalues.events.clear();
+                    self.process = None;
+                    self.metrics.status.lock().unwrap().pid = None;
+                    Ok(ReaderEvent::Detached)
+                } else {
+                    Ok(ReaderEvent::Error(e))
+                }
+            }
+        }
+    }
+
+    /// Sections that keep failing are reported
+    /// only once until they are read again
+    fn report_skipped(&mut self) {
+        for skipped in self.state.ivalues.skipped.errors.drain(..) {
+            self.metrics
+                .observe_error(skipped.section.name(), &skipped.error);
+
+            if !skipped.repeated {
+                self.pending.push_back(ReaderEvent::Skipped {
+                    section: skipped.section,
+                    error: skipped.error,
+                });
+            }
+        }
+    }
+
+    /// Reads precise values, `None` when they
+    /// aren't available right now
+    fn precise_step(&mut self) -> Option<ReaderEvent> {
+        let p = self.process.as_ref()?;
+
+        process_precise(p, &self.state, &mut self.precise).ok()?;
+
+        Some(ReaderEvent::Precise(self.precise.clone()))
+    }
+
+    fn attach(&mut self) -> Result<ReaderEvent> {
+        self.delay = Some(self.error_interval);
+
+        let p = match Process::initialize("osu!.exe", &EXCLUDE_WORDS) {
+            Ok(p) => p,
+            Err(e) => {
+                let e = Report::new(e);
+                self.metrics.set_last_error(&e);
+
+                return Ok(ReaderEvent::Error(e));
+            }
+        };
+
+        let osu_path_source = self.update_osu_path(&p)?;
+
+        let (addresses, signatures) = StaticAddresses::resolve(&p);
+        self.metrics.status.lock().unwrap().signatures = signatures;
+
+        match addresses {
+            Ok(v) => self.state.addresses = v,
+            Err(e) => {
+                self.metrics.set_last_error(&e);
+                return Ok(ReaderEvent::Error(e));
+            }
+        };
+
+        self.delay = None;
+
+        let pid = p.pid as u32;
+        self.process = Some(p);
+        self.metrics.status.lock().unwrap().pid = Some(pid);
+
+        let osu_path = self.state.values.lock().unwrap().osu_path.clone();
+
+        Ok(ReaderEvent::Attached {
+            pid,
+            osu_path,
+            osu_path_source,
+        })
+    }
+
+    fn update_osu_path(&self, p: &Process) -> Result<OsuPathSource> {
+        let mut values = self.state.values.lock().unwrap();
+
+        // OSU_PATH cli argument if provided should
+        // overwrite auto detected path
+        // else use auto detected path
+        let source = match self.osu_path {
+            Some(_) => OsuPathSource::Cli,
+            None => OsuPathSource::Auto,
+        };
+        self.metrics.status.lock().unwrap().osu_path_source = Some(source);
+
+        match self.osu_path {
+            Some(ref v) => {
+                values.osu_path.clone_from(v);
+            }
+            None => {
+                if let Some(ref dir) = p.executable_dir {
+                    values.osu_path.clone_from(dir);
+                } else {
+                    return Err(Report::msg(
+                        "Can't auto-detect osu! folder path \
+                         nor any was provided through command \
+                         line argument",
+                    ));
+                }
+            }
+        }
+
+        // Checking if path exists
+        if !values.osu_path.exists() {
+            return Err(Report::msg(format!(
+                "Provided osu path doesn't exists! Path: {}",
+                values.osu_path.display()
+            )));
+        };
+
+        Ok(source)
+    }
+}
+
+impl Iterator for Reader {
+    type Item = Result<ReaderEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.pop_front() {
+            return Some(Ok(event));
+        }
+
+        if let Some(delay) = self.delay.take() {
+            self.deadline = Some(Instant::now() + delay);
+        }
+
+        while let Some(deadline) = self.deadline {
+            let left = deadline.saturating_duration_since(Instant::now());
+
+            match self.precise_interval {
+                Some(interval) if self.ticking && interval < left => {
+                    thread::sleep(interval);
+
+                    if let Some(event) = self.precise_step() {
+                        return Some(Ok(event));
+                    }
+                }
+                _ => {
+                    thread::sleep(left);
+                    self.deadline = None;
+                }
+            }
+        }
+
+        Some(self.step())
+    }
+}
+
+fn is_process_lost(e: &Report) -> bool {
+    match e.downcast_ref::<ProcessError>() {
+        Some(&ProcessError::ProcessNotFound) => true,
+        #[cfg(target_os = "windows")]
+        Some(&ProcessError::OsError { .. }) => true,
+        Some(_) | None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_state_intervals() {
+        let default = Duration::from_millis(300);
+        let intervals = StateIntervals {
+            playing: Some(Duration::from_millis(16)),
+            song_select: None,
+            idle: Some(Duration::from_secs(1)),
+        };
+
+        assert_eq!(
+            intervals.get(GameState::Playing, default),
+            Duration::from_millis(16)
+        );
+        assert_eq!(intervals.get(GameState::SongSelect, default), default);
+        assert_eq!(
+            intervals.get(GameState::PreSongSelect, default),
+            Duration::from_secs(1)
+        );
+        assert_eq!(intervals.get(GameState::ResultScreen, default), default);
+    }
+}
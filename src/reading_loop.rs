@@ -1,20 +1,34 @@
-use std::{fs::File, io::Read, mem::size_of};
+use std::{
+    fs::File,
+    io::Read,
+    time::{Duration, Instant},
+};
 
-use eyre::Result;
+use eyre::{Report, Result};
 use rosu_map::section::events::Events;
 use rosu_pp::{Beatmap, GameMods};
 use tracy_client::*;
 
-use rosu_mem::process::{Process, ProcessTraits};
-
-use crate::structs::{BeatmapStatus, GameState, OutputValues, State};
+use crate::{
+    events::{GameEvent, PlayResult},
+    layouts::{
+        beatmap, dictionary, dictionary_entry, key, key_overlay, leaderboard, leaderboard_player,
+        mods, score, user_profile,
+    },
+    memory::MemoryReader,
+    structs::{
+        BeatmapStatus, GameState, HitErrorsNew, KeyOverlayValues, LeaderboardEntry, OutputValues,
+        SettingsValues, UserProfileValues, PRECISE_HIT_ERRORS,
+        PreciseValues, Section, State,
+    },
+};
 
 /// Here cases when key overlay is not gonna be available for reading:
 /// 1. Map is not fully loaded
 /// 2. If key overlay is not enabled in settings
 pub fn process_key_overlay(
-    p: &Process,
-    values: &mut OutputValues,
+    p: &impl MemoryReader,
+    keyoverlay: &mut KeyOverlayValues,
     ruleset_addr: i32,
 ) -> Result<()> {
     let keyoverlay_ptr = p.read_i32(ruleset_addr + 0xB0)?;
@@ -23,31 +37,224 @@ pub fn process_key_overlay(
         return Ok(());
     }
 
-    // TODO optimize using batches?
-
     let keyoverlay_addr = p.read_i32(p.read_i32(keyoverlay_ptr + 0x10)? + 0x4)?;
+    let keys = p.read_batch(keyoverlay_addr, &key_overlay::LAYOUT)?;
+
+    let k1 = p.read_batch(keys.get(key_overlay::K1), &key::LAYOUT)?;
+    keyoverlay.k1_pressed = k1.get(key::PRESSED) != 0;
+    keyoverlay.k1_count = k1.get(key::COUNT) as u32;
+
+    let k2 = p.read_batch(keys.get(key_overlay::K2), &key::LAYOUT)?;
+    keyoverlay.k2_pressed = k2.get(key::PRESSED) != 0;
+    keyoverlay.k2_count = k2.get(key::COUNT) as u32;
+
+    let m1 = p.read_batch(keys.get(key_overlay::M1), &key::LAYOUT)?;
+    keyoverlay.m1_pressed = m1.get(key::PRESSED) != 0;
+    keyoverlay.m1_count = m1.get(key::COUNT) as u32;
+
+    let m2 = p.read_batch(keys.get(key_overlay::M2), &key::LAYOUT)?;
+    keyoverlay.m2_pressed = m2.get(key::PRESSED) != 0;
+    keyoverlay.m2_count = m2.get(key::COUNT) as u32;
+
+    Ok(())
+}
+
+/// How often client settings are read
+const SETTINGS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Upper bound for the config dictionary size, osu! keeps
+/// a few hundred entries there, anything bigger is garbage
+const SETTINGS_MAX_ENTRIES: i32 = 1024;
+
+/// Reads settings that are kept in osu! config
+/// dictionary as `Bindable` values. Entries that
+/// can't be read are skipped
+pub fn process_settings(
+    p: &impl MemoryReader,
+    settings: &mut SettingsValues,
+    settings_addr: i32,
+) -> Result<()> {
+    let _span = span!("settings");
+
+    let config_ptr = p.read_i32(settings_addr + 0x10)?;
+    if config_ptr == 0 {
+        return Ok(());
+    }
+
+    let config_base = p.read_i32(config_ptr)?;
+    if config_base == 0 {
+        return Ok(());
+    }
+
+    let dict_addr = p.read_i32(config_base + 0x8)?;
+    if dict_addr == 0 {
+        return Ok(());
+    }
+
+    let dict = p.read_batch(dict_addr, &dictionary::LAYOUT)?;
+    let entries = dict.get(dictionary::ENTRIES);
+    let count = dict.get(dictionary::COUNT).clamp(0, SETTINGS_MAX_ENTRIES);
+
+    for i in 0..count {
+        let _ = process_settings_entry(p, settings, entries + 0x8 + 0x10 * i);
+    }
+
+    Ok(())
+}
 
-    values.keyoverlay.k1_pressed = p.read_i8(p.read_i32(keyoverlay_addr + 0x8)? + 0x1C)? != 0;
+fn process_settings_entry(
+    p: &impl MemoryReader,
+    settings: &mut SettingsValues,
+    entry_addr: i32,
+) -> Result<()> {
+    let read_bool = |bindable: i32| p.read_i8(bindable + 0xC).map(|v| v != 0);
+    let read_int = |bindable: i32| p.read_i32(bindable + 0x4);
 
-    values.keyoverlay.k1_count = p.read_i32(p.read_i32(keyoverlay_addr + 0x8)? + 0x14)? as u32;
+    let entry = p.read_batch(entry_addr, &dictionary_entry::LAYOUT)?;
+    let key = entry.get(dictionary_entry::KEY);
 
-    values.keyoverlay.k2_pressed = p.read_i8(p.read_i32(keyoverlay_addr + 0xC)? + 0x1C)? != 0;
+    if key == 0 {
+        return Ok(());
+    }
 
-    values.keyoverlay.k2_count = p.read_i32(p.read_i32(keyoverlay_addr + 0xC)? + 0x14)? as u32;
+    let value = entry.get(dictionary_entry::VALUE);
+
+    match p.read_string_with_limit(key, 64)?.as_str() {
+        "VolumeUniversal" => settings.volume_universal = read_int(value)?,
+        "VolumeMusic" => settings.volume_music = read_int(value)?,
+        "VolumeEffect" => settings.volume_effect = read_int(value)?,
+        "Offset" => settings.offset = read_int(value)?,
+        "DimLevel" => settings.dim_level = read_int(value)?,
+        "ManiaSpeed" => settings.mania_speed = read_int(value)?,
+        "KeyOverlay" => settings.key_overlay = read_bool(value)?,
+        "ShowInterface" => settings.show_interface = read_bool(value)?,
+        "IgnoreBeatmapSkins" => settings.ignore_beatmap_skins = read_bool(value)?,
+        "IgnoreBeatmapSamples" => settings.ignore_beatmap_samples = read_bool(value)?,
+        "Skin" => settings.skin = p.read_string_with_limit_from_ptr(value + 0x4, 300)?,
+        "Width" => settings.width = read_int(value)?,
+        "Height" => settings.height = read_int(value)?,
+        "Fullscreen" => settings.fullscreen = read_bool(value)?,
+        _ => (),
+    }
+
+    Ok(())
+}
 
-    values.keyoverlay.m1_pressed = p.read_i8(p.read_i32(keyoverlay_addr + 0x10)? + 0x1C)? != 0;
+pub fn process_user_profile(
+    p: &impl MemoryReader,
+    profile: &mut UserProfileValues,
+    user_profile_addr: i32,
+) -> Result<()> {
+    let _span = span!("user profile");
 
-    values.keyoverlay.m1_count = p.read_i32(p.read_i32(keyoverlay_addr + 0x10)? + 0x14)? as u32;
+    // Both pointers are null until the profile is loaded
+    let profile_ptr = p.read_i32(user_profile_addr + 0x7)?;
+    let profile_base = match profile_ptr {
+        0 => 0,
+        ptr => p.read_i32(ptr + 0x4)?,
+    };
 
-    values.keyoverlay.m2_pressed = p.read_i8(p.read_i32(keyoverlay_addr + 0x14)? + 0x1C)? != 0;
+    if profile_base == 0 {
+        *profile = UserProfileValues::default();
+        return Ok(());
+    }
 
-    values.keyoverlay.m2_count = p.read_i32(p.read_i32(keyoverlay_addr + 0x14)? + 0x14)? as u32;
+    let info = p.read_batch(profile_base, &user_profile::LAYOUT)?;
+
+    *profile = UserProfileValues {
+        name: p.read_string_with_limit(info.get(user_profile::NAME), 30)?,
+        user_id: info.get(user_profile::USER_ID),
+        accuracy: info.get(user_profile::ACCURACY),
+        ranked_score: info.get(user_profile::RANKED_SCORE),
+        level: info.get(user_profile::LEVEL),
+        play_count: info.get(user_profile::PLAY_COUNT),
+        play_mode: info.get(user_profile::PLAY_MODE),
+        rank: info.get(user_profile::RANK),
+        pp: info.get(user_profile::PP),
+        bancho_status: info.get(user_profile::BANCHO_STATUS),
+        country_code: info.get(user_profile::COUNTRY_CODE),
+    };
+
+    Ok(())
+}
+
+/// Upper bound for the leaderboard slots, size of a
+/// stale list can be anything and is read before items
+const LEADERBOARD_MAX_PLAYERS: i32 = 256;
+
+/// Reads slots of the in-game leaderboard. Leaderboard is owned by
+/// the ruleset, so it's reached through `[ruleset + 0x7C] + 0x24`
+/// instead of a separate static signature, `rulesets` one is
+/// already resolved and scanning memory for one more is slow
+pub fn process_leaderboard(
+    p: &impl MemoryReader,
+    entries: &mut Vec<LeaderboardEntry>,
+    ruleset_addr: i32,
+) -> Result<()> {
+    let _span = span!("leaderboard");
+
+    entries.clear();
+
+    let leaderboard_base = p.read_i32(ruleset_addr + 0x7C)?;
+
+    if leaderboard_base == 0 {
+        return Ok(());
+    }
+
+    let leaderboard_addr = p.read_i32(leaderboard_base + 0x24)?;
+
+    if leaderboard_addr == 0 {
+        return Ok(());
+    }
+
+    let board = p.read_batch(leaderboard_addr, &leaderboard::LAYOUT)?;
+    let our_player = board.get(leaderboard::OUR_PLAYER);
+
+    let players_addr = board.get(leaderboard::PLAYERS);
+    let players_count = p.read_i32(players_addr + 0xC)?;
+
+    if !(0..=LEADERBOARD_MAX_PLAYERS).contains(&players_count) {
+        return Err(Report::msg(format!(
+            "Invalid leaderboard size: {players_count}"
+        )));
+    }
+
+    let mut players = Vec::new();
+    p.read_i32_array(players_addr, &mut players)?;
+
+    for player_addr in players {
+        let player = p.read_batch(player_addr, &leaderboard_player::LAYOUT)?;
+        let score_entry = player.get(leaderboard_player::SCORE_ENTRY);
+
+        if score_entry == 0 {
+            continue;
+        }
+
+        let score_info = p.read_batch(score_entry, &score::LAYOUT)?;
+        let mods_xor = p.read_batch(score_info.get(score::MODS), &mods::LAYOUT)?;
+
+        entries.push(LeaderboardEntry {
+            name: p.read_string_with_limit(player.get(leaderboard_player::NAME), 30)?,
+            score: player.get(leaderboard_player::SCORE),
+            combo: score_info.get(score::COMBO),
+            max_combo: score_info.get(score::MAX_COMBO),
+            mods: mods_xor.get(mods::XOR1) ^ mods_xor.get(mods::XOR2),
+            hit_300: score_info.get(score::HIT_300),
+            hit_100: score_info.get(score::HIT_100),
+            hit_50: score_info.get(score::HIT_50),
+            hit_miss: score_info.get(score::HIT_MISS),
+            team: player.get(leaderboard_player::TEAM),
+            position: player.get(leaderboard_player::POSITION),
+            is_passing: player.get(leaderboard_player::IS_PASSING) != 0,
+            is_ourself: player_addr == our_player,
+        });
+    }
 
     Ok(())
 }
 
 pub fn process_gameplay(
-    p: &Process,
+    p: &impl MemoryReader,
     state: &mut State,
     values: &mut OutputValues,
     ruleset_addr: i32,
@@ -55,6 +262,14 @@ pub fn process_gameplay(
     let _span = span!("Gameplay data");
 
     if values.prev_playtime > values.playtime {
+        // `prev_playtime` is zero right after entering `Playing` state
+        if values.prev_state == GameState::Playing && values.prev_playtime > 0 {
+            state.ivalues.events.push(GameEvent::PlayRetried {
+                map_id: values.beatmap.map_id,
+                mods: values.gameplay.mods,
+            });
+        }
+
         values.reset_gameplay(&mut state.ivalues);
         state.ivalues.reset();
     }
@@ -83,38 +298,41 @@ pub fn process_gameplay(
         values.gameplay.current_hp_smooth = p.read_f64(hp_base + 0x14)?;
     }
 
-    let hit_errors_base = p.read_i32(score_base + 0x38)?;
+    let score_info = p.read_batch(score_base, &score::LAYOUT)?;
+
+    let hit_errors_base = score_info.get(score::HIT_ERRORS);
 
-    p.read_i32_array(hit_errors_base, &mut values.gameplay.hit_errors)?;
+    // Only new hit errors are read, list is
+    // read from scratch when it's a different one
+    if state.ivalues.hit_errors_addr != hit_errors_base {
+        state.ivalues.hit_errors_addr = hit_errors_base;
+        values.gameplay.hit_errors.clear();
+    }
+
+    let hit_errors = &mut values.gameplay.hit_errors;
+    let index = p.read_i32_array_append(hit_errors_base, hit_errors)?;
+
+    if index == 0 {
+        values.gameplay.hit_errors_generation =
+            values.gameplay.hit_errors_generation.wrapping_add(1);
+    }
+
+    values.gameplay.hit_errors_new = HitErrorsNew {
+        index,
+        values: hit_errors[index..].to_vec(),
+    };
 
     values.gameplay.unstable_rate = values.gameplay.calculate_unstable_rate();
-    values.gameplay.mode = p.read_i32(score_base + 0x64)?;
-
-    let mut score_info_buff = [0u8; size_of::<i16>() * 6];
-
-    p.read(
-        score_base + 0x88,
-        size_of::<i16>() * 6,
-        &mut score_info_buff,
-    )?;
-
-    // Safety: Already filled with zeros & bounds are correct
-    unsafe {
-        values.gameplay.hit_100 =
-            i16::from_le_bytes(score_info_buff[0..2].try_into().unwrap_unchecked());
-        values.gameplay.hit_300 =
-            i16::from_le_bytes(score_info_buff[2..4].try_into().unwrap_unchecked());
-        values.gameplay.hit_50 =
-            i16::from_le_bytes(score_info_buff[4..6].try_into().unwrap_unchecked());
-        values.gameplay.hit_geki =
-            i16::from_le_bytes(score_info_buff[6..8].try_into().unwrap_unchecked());
-        values.gameplay.hit_katu =
-            i16::from_le_bytes(score_info_buff[8..10].try_into().unwrap_unchecked());
-        values.gameplay.hit_miss =
-            i16::from_le_bytes(score_info_buff[10..].try_into().unwrap_unchecked());
-    }
-
-    values.gameplay.username = p.read_string_with_limit_from_ptr(score_base + 0x28, 30)?;
+    values.gameplay.mode = score_info.get(score::MODE);
+
+    values.gameplay.hit_100 = score_info.get(score::HIT_100);
+    values.gameplay.hit_300 = score_info.get(score::HIT_300);
+    values.gameplay.hit_50 = score_info.get(score::HIT_50);
+    values.gameplay.hit_geki = score_info.get(score::HIT_GEKI);
+    values.gameplay.hit_katu = score_info.get(score::HIT_KATU);
+    values.gameplay.hit_miss = score_info.get(score::HIT_MISS);
+
+    values.gameplay.username = p.read_string_with_limit(score_info.get(score::USERNAME), 30)?;
 
     let passed_objects = values.gameplay.passed_objects()?;
 
@@ -122,9 +340,9 @@ pub fn process_gameplay(
 
     values.gameplay.update_accuracy();
 
-    values.gameplay.score = p.read_i32(score_base + 0x78)?;
-    values.gameplay.combo = p.read_i16(score_base + 0x94)?;
-    values.gameplay.max_combo = p.read_i16(score_base + 0x68)?;
+    values.gameplay.score = score_info.get(score::SCORE);
+    values.gameplay.combo = score_info.get(score::COMBO);
+    values.gameplay.max_combo = score_info.get(score::MAX_COMBO);
 
     if values.gameplay.combo < values.prev_combo && values.gameplay.hit_miss == values.prev_hit_miss
     {
@@ -133,17 +351,12 @@ pub fn process_gameplay(
 
     values.prev_hit_miss = values.gameplay.hit_miss;
 
-    let mods_xor_base = p.read_i32(score_base + 0x1C)?;
-
-    let mods_raw = p.read_u64(mods_xor_base + 0x8)?;
-
-    let mods_xor1 = mods_raw & 0xFFFFFFFF;
-    let mods_xor2 = mods_raw >> 32;
+    let mods_xor = p.read_batch(score_info.get(score::MODS), &mods::LAYOUT)?;
 
     // Read key overlay
-    process_key_overlay(p, values, ruleset_addr)?;
+    process_key_overlay(p, &mut values.keyoverlay, ruleset_addr)?;
 
-    values.gameplay.mods = (mods_xor1 ^ mods_xor2) as u32;
+    values.gameplay.mods = mods_xor.get(mods::XOR1) ^ mods_xor.get(mods::XOR2);
     values.update_readable_mods();
 
     // Calculate pp
@@ -157,10 +370,63 @@ pub fn process_gameplay(
     values.update_current_bpm();
     values.update_kiai();
 
+    // Leaderboard is not essential, it shouldn't
+    // take the rest of gameplay values with it
+    let res = process_leaderboard(p, &mut values.leaderboard, ruleset_addr);
+
+    if !state.ivalues.skipped.report(Section::Leaderboard, res) {
+        values.leaderboard.clear();
+    }
+
+    Ok(())
+}
+
+/// Reads only values that change every frame, used between
+/// regular ticks. Shared values are not touched
+pub fn process_precise(
+    p: &impl MemoryReader,
+    state: &State,
+    values: &mut PreciseValues,
+) -> Result<()> {
+    let _span = span!("precise values");
+
+    let audio_time_ptr = p.read_i32(state.addresses.audio_time_base + 0x9)?;
+    values.precise_audio_time = p.read_i32(audio_time_ptr)?;
+
+    let status_ptr = p.read_i32(state.addresses.status - 0x4)?;
+
+    if GameState::from(p.read_u32(status_ptr)?) != GameState::Playing {
+        values.keyoverlay.reset();
+        values.hit_errors.clear();
+        values.hit_errors_count = 0;
+
+        return Ok(());
+    }
+
+    let ruleset_addr = p.read_i32(p.read_i32(state.addresses.rulesets - 0xb)? + 0x4)?;
+
+    if ruleset_addr == 0 {
+        return Ok(());
+    }
+
+    process_key_overlay(p, &mut values.keyoverlay, ruleset_addr)?;
+
+    let gameplay_base = p.read_i32(ruleset_addr + 0x68)?;
+
+    if gameplay_base == 0 {
+        return Ok(());
+    }
+
+    let score_base = p.read_i32(gameplay_base + 0x38)?;
+    let hit_errors_base = p.read_i32(score_base + 0x38)?;
+
+    values.hit_errors_count =
+        p.read_i32_array_tail(hit_errors_base, PRECISE_HIT_ERRORS, &mut values.hit_errors)?;
+
     Ok(())
 }
 
-pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
+pub fn process_reading_loop(p: &impl MemoryReader, state: &mut State) -> Result<()> {
     let _span = span!("reading loop");
 
     let values = state.values.clone();
@@ -170,16 +436,48 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
 
     let menu_mods = p.read_u32(menu_mods_ptr)?;
     values.menu_mods = menu_mods;
+    state.ivalues.raw.menu_mods = menu_mods;
 
     let playtime_ptr = p.read_i32(state.addresses.playtime + 0x5)?;
     values.playtime = p.read_i32(playtime_ptr)?;
+    state.ivalues.raw.playtime = values.playtime;
 
     let beatmap_ptr = p.read_i32(state.addresses.base - 0xC)?;
     let beatmap_addr = p.read_i32(beatmap_ptr)?;
+    state.ivalues.raw.beatmap_addr = beatmap_addr;
 
     let status_ptr = p.read_i32(state.addresses.status - 0x4)?;
 
-    values.state = GameState::from(p.read_u32(status_ptr)?);
+    let status = p.read_u32(status_ptr)?;
+    state.ivalues.raw.status = status;
+
+    values.state = GameState::from(status);
+
+    if state.ivalues.last_state != values.state {
+        let from = state.ivalues.last_state;
+
+        if from == GameState::Playing {
+            let play = PlayResult::from(&*values);
+
+            let event = if values.state == GameState::ResultScreen {
+                GameEvent::PlayCompleted(play)
+            } else {
+                GameEvent::PlayFailed {
+                    quit: values.gameplay.current_hp > 0.0,
+                    play,
+                }
+            };
+
+            state.ivalues.events.push(event);
+        }
+
+        state.ivalues.events.push(GameEvent::StateChanged {
+            from,
+            to: values.state,
+        });
+
+        state.ivalues.last_state = values.state;
+    }
 
     // Handle leaving `Playing` state
     if values.prev_state == GameState::Playing && values.state != GameState::Playing {
@@ -192,41 +490,28 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
         return Ok(());
     }
 
+    let beatmap_info = p.read_batch(beatmap_addr, &beatmap::LAYOUT)?;
+
     if values.state != GameState::MultiplayerLobby {
-        let mut beatmap_stats_buff = [0u8; size_of::<f32>() * 4];
-
-        p.read(
-            beatmap_addr + 0x2c,
-            size_of::<f32>() * 4,
-            &mut beatmap_stats_buff,
-        )?;
-
-        // Safety: `buff` is already initialized
-        // and filled with zeros, the worst case scenario is
-        // ar, cs, od, hp going to be zero's
-        unsafe {
-            values.beatmap.ar =
-                f32::from_le_bytes(beatmap_stats_buff[0..4].try_into().unwrap_unchecked());
-            values.beatmap.cs =
-                f32::from_le_bytes(beatmap_stats_buff[4..8].try_into().unwrap_unchecked());
-            values.beatmap.hp =
-                f32::from_le_bytes(beatmap_stats_buff[8..12].try_into().unwrap_unchecked());
-            values.beatmap.od =
-                f32::from_le_bytes(beatmap_stats_buff[12..].try_into().unwrap_unchecked());
-        }
+        values.beatmap.ar = beatmap_info.get(beatmap::AR);
+        values.beatmap.cs = beatmap_info.get(beatmap::CS);
+        values.beatmap.hp = beatmap_info.get(beatmap::HP);
+        values.beatmap.od = beatmap_info.get(beatmap::OD);
 
         let plays_addr = p.read_i32(state.addresses.base - 0x33)? + 0xC;
         values.plays = p.read_i32(plays_addr)?;
 
-        values.beatmap.artist = p.read_string_with_limit_from_ptr(beatmap_addr + 0x18, 100)?;
-        values.beatmap.title = p.read_string_with_limit_from_ptr(beatmap_addr + 0x24, 150)?;
-        values.beatmap.creator = p.read_string_with_limit_from_ptr(beatmap_addr + 0x7C, 30)?;
-        values.beatmap.difficulty = p.read_string_with_limit_from_ptr(beatmap_addr + 0xAC, 30)?;
-        values.beatmap.map_id = p.read_i32(beatmap_addr + 0xC8)?; // TODO batch
-        values.beatmap.mapset_id = p.read_i32(beatmap_addr + 0xCC)?; // TODO batch
+        values.beatmap.artist = p.read_string_with_limit(beatmap_info.get(beatmap::ARTIST), 100)?;
+        values.beatmap.title = p.read_string_with_limit(beatmap_info.get(beatmap::TITLE), 150)?;
+        values.beatmap.creator =
+            p.read_string_with_limit(beatmap_info.get(beatmap::CREATOR), 30)?;
+        values.beatmap.difficulty =
+            p.read_string_with_limit(beatmap_info.get(beatmap::DIFFICULTY), 30)?;
+        values.beatmap.map_id = beatmap_info.get(beatmap::MAP_ID);
+        values.beatmap.mapset_id = beatmap_info.get(beatmap::MAPSET_ID);
     }
 
-    values.beatmap.beatmap_status = BeatmapStatus::from(p.read_i16(beatmap_addr + 0x12C)?);
+    values.beatmap.beatmap_status = BeatmapStatus::from(beatmap_info.get(beatmap::STATUS));
 
     let mut new_map = false;
 
@@ -242,23 +527,49 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
     values.skin_folder = values.osu_path.join("Skin").join(&skin_name);
     values.skin = skin_name;
 
+    // User profile is optional, same as settings below
+    if let Some(user_profile_addr) = state.addresses.user_profile {
+        let res = process_user_profile(p, &mut values.user_profile, user_profile_addr);
+
+        if !state.ivalues.skipped.report(Section::UserProfile, res) {
+            values.user_profile = UserProfileValues::default();
+        }
+    }
+
+    // Settings rarely change, broken ones are
+    // retried only after the interval as well
+    if let Some(settings_addr) = state.addresses.settings
+        && state
+            .ivalues
+            .settings_updated
+            .is_none_or(|v| v.elapsed() >= SETTINGS_INTERVAL)
+    {
+        state.ivalues.settings_updated = Some(Instant::now());
+
+        // Settings are optional, their errors shouldn't
+        // stop the rest of the values from being read
+        let res = process_settings(p, &mut values.settings, settings_addr);
+        state.ivalues.skipped.report(Section::Settings, res);
+    }
+
     if values.state != GameState::PreSongSelect
         && values.state != GameState::MultiplayerLobby
         && values.state != GameState::MultiplayerResultScreen
     {
         let menu_mode_addr = p.read_i32(state.addresses.base - 0x33)?;
 
-        let beatmap_file = p.read_string_with_limit_from_ptr(beatmap_addr + 0x90, 300)?;
-        let beatmap_folder = p.read_string_with_limit_from_ptr(beatmap_addr + 0x78, 300)?;
-        let audio_file = p.read_string_with_limit_from_ptr(beatmap_addr + 0x64, 150)?;
+        let beatmap_file = p.read_string_with_limit(beatmap_info.get(beatmap::FILE), 300)?;
+        let beatmap_folder = p.read_string_with_limit(beatmap_info.get(beatmap::FOLDER), 300)?;
+        let audio_file = p.read_string_with_limit(beatmap_info.get(beatmap::AUDIO_FILE), 150)?;
         values.menu_mode = p.read_i32(menu_mode_addr)?;
+        state.ivalues.raw.menu_mode = values.menu_mode;
 
         values.beatmap.paths.beatmap_full_path = values.osu_path.join("Songs/");
 
         values.beatmap.paths.beatmap_full_path.push(&beatmap_folder);
         values.beatmap.paths.beatmap_full_path.push(&beatmap_file);
 
-        values.beatmap.md5 = p.read_string_with_limit_from_ptr(beatmap_addr + 0x6C, 50)?;
+        values.beatmap.md5 = p.read_string_with_limit(beatmap_info.get(beatmap::MD5), 50)?;
 
         // Check if beatmap changed
         if (beatmap_folder != values.beatmap.paths.beatmap_folder
@@ -275,6 +586,21 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
             let current_beatmap = match Beatmap::from_bytes(&beatmap_buff) {
                 Ok(beatmap) => {
                     new_map = true;
+                    state.ivalues.skipped.report(Section::Beatmap, Ok(()));
+
+                    if beatmap_folder != values.beatmap.paths.beatmap_folder
+                        || beatmap_file != values.beatmap.paths.beatmap_file
+                    {
+                        state.ivalues.events.push(GameEvent::BeatmapChanged {
+                            map_id: values.beatmap.map_id,
+                            mapset_id: values.beatmap.mapset_id,
+                            md5: values.beatmap.md5.clone(),
+                            artist: values.beatmap.artist.clone(),
+                            title: values.beatmap.title.clone(),
+                            creator: values.beatmap.creator.clone(),
+                            difficulty: values.beatmap.difficulty.clone(),
+                        });
+                    }
 
                     values
                         .beatmap
@@ -294,8 +620,9 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
 
                     Some(beatmap)
                 }
-                Err(_) => {
-                    println!("Failed to parse beatmap");
+                Err(e) => {
+                    state.ivalues.skipped.report(Section::Beatmap, Err(e.into()));
+                    state.ivalues.beatmap_parse_failures += 1;
                     None
                 }
             };
@@ -325,9 +652,11 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
     }
 
     let ruleset_addr = p.read_i32(p.read_i32(state.addresses.rulesets - 0xb)? + 0x4)?;
+    state.ivalues.raw.ruleset_addr = ruleset_addr;
 
     let audio_time_ptr = p.read_i32(state.addresses.audio_time_base + 0x9)?;
     values.precise_audio_time = p.read_i32(audio_time_ptr)?;
+    state.ivalues.raw.precise_audio_time = values.precise_audio_time;
 
     // If this happened there is zero sense to continue
     // reading because all the values depends on this
@@ -340,39 +669,23 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
     // TODO handle situations when result screen is not ready
     if values.state == GameState::ResultScreen {
         let result_base = p.read_i32(ruleset_addr + 0x38)?;
+        let result = p.read_batch(result_base, &score::LAYOUT)?;
 
         values.result_screen.username =
-            p.read_string_with_limit_from_ptr(result_base + 0x28, 30)?;
-
-        let mods_xor_base = p.read_i32(result_base + 0x1C)?;
-
-        // TODO batch
-        let mods_xor1 = p.read_i32(mods_xor_base + 0xC)?;
-        let mods_xor2 = p.read_i32(mods_xor_base + 0x8)?;
-
-        values.result_screen.mods = (mods_xor1 ^ mods_xor2) as u32;
-        values.result_screen.mode = p.read_i32(result_base + 0x64)? as u8;
-        values.result_screen.score = p.read_i32(result_base + 0x78)?;
-
-        let mut info_buff = [0u8; size_of::<i16>() * 6];
-
-        p.read(result_base + 0x88, size_of::<i16>() * 6, &mut info_buff)?;
-
-        // Safety: Already filled with zeros & bounds are correct
-        unsafe {
-            values.result_screen.hit_100 =
-                i16::from_le_bytes(info_buff[0..2].try_into().unwrap_unchecked());
-            values.result_screen.hit_300 =
-                i16::from_le_bytes(info_buff[2..4].try_into().unwrap_unchecked());
-            values.result_screen.hit_50 =
-                i16::from_le_bytes(info_buff[4..6].try_into().unwrap_unchecked());
-            values.result_screen.hit_geki =
-                i16::from_le_bytes(info_buff[6..8].try_into().unwrap_unchecked());
-            values.result_screen.hit_katu =
-                i16::from_le_bytes(info_buff[8..10].try_into().unwrap_unchecked());
-            values.result_screen.hit_miss =
-                i16::from_le_bytes(info_buff[10..].try_into().unwrap_unchecked());
-        }
+            p.read_string_with_limit(result.get(score::USERNAME), 30)?;
+
+        let mods_xor = p.read_batch(result.get(score::MODS), &mods::LAYOUT)?;
+
+        values.result_screen.mods = mods_xor.get(mods::XOR1) ^ mods_xor.get(mods::XOR2);
+        values.result_screen.mode = result.get(score::MODE) as u8;
+        values.result_screen.score = result.get(score::SCORE);
+
+        values.result_screen.hit_100 = result.get(score::HIT_100);
+        values.result_screen.hit_300 = result.get(score::HIT_300);
+        values.result_screen.hit_50 = result.get(score::HIT_50);
+        values.result_screen.hit_geki = result.get(score::HIT_GEKI);
+        values.result_screen.hit_katu = result.get(score::HIT_KATU);
+        values.result_screen.hit_miss = result.get(score::HIT_MISS);
 
         values.result_screen.update_accuracy();
     }
@@ -381,10 +694,8 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
     if values.state == GameState::Playing {
         let res = process_gameplay(p, state, &mut values, ruleset_addr);
 
-        if let Err(e) = res {
-            println!("{:?}", e);
-            println!("Skipped gameplay reading, probably it's not ready yet");
-        }
+        // Usually it's not ready yet
+        state.ivalues.skipped.report(Section::Gameplay, res);
     }
 
     // Handling entering `ResultScreen` state
@@ -410,6 +721,14 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
 
     // Update stars when entering `Playing` state
     if values.prev_state != GameState::Playing && values.state == GameState::Playing {
+        // Gameplay values are usually not read yet on this tick,
+        // menu mods and mode are the ones play is started with
+        state.ivalues.events.push(GameEvent::PlayStarted {
+            map_id: values.beatmap.map_id,
+            mods: values.menu_mods,
+            mode: values.menu_mode,
+        });
+
         values.reset_gameplay(&mut state.ivalues);
         values.update_stars_and_ss_pp();
         values.adjust_bpm();
@@ -417,6 +736,11 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
 
     // Handle mods changes inside `SongSelect` state
     if values.state == GameState::SongSelect && values.prev_menu_mods != values.menu_mods {
+        state.ivalues.events.push(GameEvent::ModsChanged {
+            from: values.prev_menu_mods,
+            to: values.menu_mods,
+        });
+
         values.update_stars_and_ss_pp();
         values.update_current_pp(&mut state.ivalues);
         values.adjust_bpm();
@@ -428,3 +752,663 @@ pub fn process_reading_loop(p: &Process, state: &mut State) -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{
+        memory::FakeMemory,
+        structs::{InnerValues, SkippedSection, StaticAddresses},
+    };
+
+    const BASE: i32 = 0x1000;
+    const STATUS: i32 = 0x2000;
+    const MENU_MODS: i32 = 0x3000;
+    const RULESETS: i32 = 0x4000;
+    const PLAYTIME: i32 = 0x5000;
+    const SKIN: i32 = 0x6000;
+    const CHAT_CHECKER: i32 = 0x7000;
+    const AUDIO_TIME_BASE: i32 = 0x8000;
+    const SETTINGS: i32 = 0x9000;
+    const USER_PROFILE: i32 = 0xA000;
+
+    const STATUS_VALUE: i32 = 0x10030;
+    const PLAYTIME_VALUE: i32 = 0x10010;
+    const BEATMAP: i32 = 0x20000;
+    const RULESET: i32 = 0x40000;
+    const RESULT_SCREEN: i32 = 0x50000;
+    const GAMEPLAY: i32 = 0x60000;
+    const SCORE: i32 = 0x61000;
+    const LEADERBOARD: i32 = 0x71000;
+
+    /// Memory image of the osu! sitting in `SongSelect`
+    fn memory_image() -> FakeMemory {
+        let mut m = FakeMemory::default();
+
+        m.signature("F8 01 74 04 83 65", BASE)
+            .signature("48 83 F8 04 73 1E", STATUS)
+            .signature(
+                "C8 FF ?? ?? ?? ?? ?? 81 0D ?? ?? ?? ?? 00 08 00 00",
+                MENU_MODS,
+            )
+            .signature("7D 15 A1 ?? ?? ?? ?? 85 C0", RULESETS)
+            .signature("5E 5F 5D C3 A1 ?? ?? ?? ?? 89 ?? 04", PLAYTIME)
+            .signature("74 2C 85 FF 75 28 A1 ?? ?? ?? ?? 8D 15", SKIN)
+            .signature("0A D7 23 3C 00 00 ?? 01", CHAT_CHECKER)
+            .signature("DB 5C 24 34 8B 44 24 34", AUDIO_TIME_BASE)
+            .signature(
+                "7E 07 8D 65 F8 5E 5F 5D C3 E8 ?? ?? ?? ?? 8B 0D ?? ?? ?? ?? 33 D2 39 09 FF 15",
+                SETTINGS,
+            )
+            .signature("FF 15 ?? ?? ?? ?? A1 ?? ?? ?? ?? 8B 48 54 33 D2", USER_PROFILE);
+
+        // Static pointers
+        m.write_i32(MENU_MODS + 0x9, 0x10000)
+            .write_u32(0x10000, 0)
+            .write_i32(PLAYTIME + 0x5, PLAYTIME_VALUE)
+            .write_i32(PLAYTIME_VALUE, 0)
+            .write_i32(BASE - 0xC, 0x10020)
+            .write_i32(0x10020, BEATMAP)
+            .write_i32(STATUS - 0x4, STATUS_VALUE)
+            .write_u32(STATUS_VALUE, 5)
+            .write_i32(BASE - 0x33, 0x10040)
+            .write_i32(0x10040, 0) // menu mode
+            .write_i32(0x10040 + 0xC, 3) // plays
+            .write_i8(CHAT_CHECKER - 0x20, 1)
+            .write_i32(SKIN + 0x7, 0x10050)
+            .write_i32(0x10050, 0x30000)
+            .write_string(0x30000 + 0x44, 0x31000, "lain's skin")
+            .write_i32(RULESETS - 0xb, 0x10060)
+            .write_i32(0x10060 + 0x4, RULESET)
+            .write_i32(AUDIO_TIME_BASE + 0x9, 0x10070)
+            .write_i32(0x10070, 1234);
+
+        // User profile
+        m.write_i32(USER_PROFILE + 0x7, 0x10090)
+            .write_i32(0x10090 + 0x4, 0x90000)
+            .write(0x90000, &[0; 0xA0])
+            .write_f64(0x90000 + 0x4, 98.7)
+            .write_u64(0x90000 + 0xC, 40_000_000_000)
+            .write_string(0x90000 + 0x30, 0x91000, "Vaxei")
+            .write_i32(0x90000 + 0x70, 4787150)
+            .write_i32(0x90000 + 0x84, 3)
+            .write_i16(0x90000 + 0x88, 21000)
+            .write_i32(0x90000 + 0x9C, 225);
+
+        // Settings, second entry is removed
+        m.write_i32(SETTINGS + 0x10, 0x10080)
+            .write_i32(0x10080, 0x80000)
+            .write_i32(0x80000 + 0x8, 0x81000)
+            .write(0x81000, &[0; 0x20])
+            .write_i32(0x81000 + 0x8, 0x82000)
+            .write_i32(0x81000 + 0x1C, 5);
+
+        for (i, key) in ["DimLevel", "", "KeyOverlay", "Skin", "EditorTip"]
+            .iter()
+            .enumerate()
+        {
+            let entry = 0x82000 + 0x8 + 0x10 * i as i32;
+            let bindable = 0x84000 + 0x100 * i as i32;
+
+            if key.is_empty() {
+                m.write_i32(entry, 0).write_i32(entry + 0x4, 0);
+                continue;
+            }
+
+            m.write_string(entry, 0x83000 + 0x100 * i as i32, key)
+                .write_i32(entry + 0x4, bindable)
+                .write_i32(bindable + 0x4, 80)
+                .write_i8(bindable + 0xC, 1);
+        }
+
+        m.write_string(0x84300 + 0x4, 0x84380, "lain's skin");
+
+        // Beatmap, fields are read in one batch
+        m.write(BEATMAP, &[0; 0x130]);
+        m.write_f32(BEATMAP + 0x2C, 9.6)
+            .write_f32(BEATMAP + 0x30, 4.2)
+            .write_f32(BEATMAP + 0x34, 5.0)
+            .write_f32(BEATMAP + 0x38, 9.0)
+            .write_string(BEATMAP + 0x18, 0x21000, "Eternal Tears Of Sorrow")
+            .write_string(BEATMAP + 0x24, 0x21200, "Another Me")
+            .write_string(BEATMAP + 0x7C, 0x21400, "LMT")
+            .write_string(BEATMAP + 0xAC, 0x21600, "Insane")
+            .write_string(BEATMAP + 0x90, 0x21800, "map.osu")
+            .write_string(BEATMAP + 0x78, 0x21A00, "1271974 Another Me")
+            .write_string(BEATMAP + 0x64, 0x21C00, "audio.mp3")
+            .write_string(BEATMAP + 0x6C, 0x21E00, "d41d8cd98f00b204e9800998ecf8427e")
+            .write_i32(BEATMAP + 0xC8, 2643167)
+            .write_i32(BEATMAP + 0xCC, 1271974)
+            .write_i16(BEATMAP + 0x12C, 4);
+
+        // Ruleset
+        m.write_i32(RULESET + 0x38, RESULT_SCREEN)
+            .write_i32(RULESET + 0x68, GAMEPLAY)
+            .write_i32(RULESET + 0x7C, 0x70000)
+            .write_i32(RULESET + 0xB0, 0);
+
+        // Gameplay
+        let mods_key = 0x1234_5678u64;
+        m.write(SCORE, &[0; 0x98]);
+        m.write_i32(GAMEPLAY + 0x38, SCORE)
+            .write_i32(GAMEPLAY + 0x40, 0x62000)
+            .write_f64(0x62000 + 0x1C, 200.0)
+            .write_f64(0x62000 + 0x14, 190.0)
+            .write_i32(SCORE + 0x38, 0x63000)
+            .write_i32_list(0x63000, 0x64000, &[2, -3, 10, -7])
+            .write_i32(SCORE + 0x64, 0)
+            .write_string(SCORE + 0x28, 0x66000, "Vaxei")
+            .write_i32(SCORE + 0x78, 120917)
+            .write_i16(SCORE + 0x94, 5)
+            .write_i16(SCORE + 0x68, 8)
+            .write_i32(SCORE + 0x1C, 0x65000)
+            .write_u64(0x65000 + 0x8, (mods_key << 32) | (mods_key ^ 72));
+
+        for (i, hit) in [2i16, 10, 0, 0, 0, 1].iter().enumerate() {
+            m.write_i16(SCORE + 0x88 + i as i32 * 2, *hit);
+        }
+
+        // Leaderboard, second slot is ourselves
+        m.write_i32(0x70000 + 0x24, LEADERBOARD)
+            .write(LEADERBOARD, &[0; 0x14])
+            .write_i32(LEADERBOARD + 0x4, 0x71800)
+            .write_i32_list(0x71800, 0x72000, &[0x73000, 0x74000])
+            .write_i32(LEADERBOARD + 0x10, 0x74000);
+
+        for (player, name, score_entry, position) in [
+            (0x73000, "mrekk", 0x75000, 1),
+            (0x74000, "Vaxei", SCORE, 2),
+        ] {
+            m.write(player, &[0; 0x4C])
+                .write_string(player + 0x8, player + 0x100, name)
+                .write_i32(player + 0x20, score_entry)
+                .write_i32(player + 0x2C, position)
+                .write_i32(player + 0x30, 1_000_000 / position)
+                .write_i8(player + 0x4B, 1);
+        }
+
+        m.write(0x75000, &[0; 0x98])
+            .write_i32(0x75000 + 0x1C, 0x76000)
+            .write_i32(0x76000 + 0xC, 8)
+            .write_i32(0x76000 + 0x8, 0)
+            .write_i16(0x75000 + 0x68, 1811)
+            .write_i16(0x75000 + 0x8A, 1115);
+
+        // Result screen
+        m.write(RESULT_SCREEN, &[0; 0x98]);
+        m.write_string(RESULT_SCREEN + 0x28, 0x52000, "Vaxei")
+            .write_i32(RESULT_SCREEN + 0x1C, 0x51000)
+            .write_i32(0x51000 + 0xC, 0x7F)
+            .write_i32(0x51000 + 0x8, 0x7F ^ 16)
+            .write_i32(RESULT_SCREEN + 0x64, 0)
+            .write_i32(RESULT_SCREEN + 0x78, 500000);
+
+        for (i, hit) in [5i16, 90, 1, 0, 0, 2].iter().enumerate() {
+            m.write_i16(RESULT_SCREEN + 0x88 + i as i32 * 2, *hit);
+        }
+
+        m
+    }
+
+    fn state(m: &FakeMemory) -> State {
+        State {
+            addresses: StaticAddresses::new(m).unwrap(),
+            values: Arc::new(Mutex::new(OutputValues::default())),
+            ivalues: InnerValues::default(),
+        }
+    }
+
+    #[test]
+    fn test_static_addresses() {
+        let m = memory_image();
+        let addresses = StaticAddresses::new(&m).unwrap();
+
+        assert_eq!(addresses.base, BASE);
+        assert_eq!(addresses.audio_time_base, AUDIO_TIME_BASE);
+
+        let mut m = FakeMemory::default();
+        m.signature("F8 01 74 04 83 65", BASE);
+        assert!(StaticAddresses::new(&m).is_err());
+
+        let (res, statuses) = StaticAddresses::resolve(&m);
+        assert!(res.is_err());
+        assert_eq!(statuses.len(), 10);
+        assert_eq!(statuses[0].name, "base");
+        assert_eq!(statuses[0].address, Some(BASE));
+        assert!(statuses[1..].iter().all(|v| v.address.is_none()));
+
+        // Settings signature is optional
+        let mut m = memory_image();
+        m.remove_signature(
+            "7E 07 8D 65 F8 5E 5F 5D C3 E8 ?? ?? ?? ?? 8B 0D ?? ?? ?? ?? 33 D2 39 09 FF 15",
+        );
+
+        let (res, statuses) = StaticAddresses::resolve(&m);
+        assert_eq!(res.unwrap().settings, None);
+        assert!(
+            statuses
+                .iter()
+                .any(|v| v.name == "settings" && v.address.is_none())
+        );
+
+        let mut state = state(&m);
+        process_reading_loop(&m, &mut state).unwrap();
+        {
+            let values = state.values.lock().unwrap();
+            assert_eq!(values.settings, SettingsValues::default());
+            assert_eq!(values.beatmap.title, "Another Me");
+        }
+    }
+
+    #[test]
+    fn test_process_user_profile() {
+        let mut m = memory_image();
+        let mut state = state(&m);
+
+        process_reading_loop(&m, &mut state).unwrap();
+        assert_eq!(state.values.lock().unwrap().user_profile.name, "Vaxei");
+
+        // Profile isn't loaded yet
+        for (addr, ptr) in [(0x10090 + 0x4, 0), (USER_PROFILE + 0x7, 0)] {
+            m.write_i32(addr, ptr);
+            process_reading_loop(&m, &mut state).unwrap();
+            assert_eq!(
+                state.values.lock().unwrap().user_profile,
+                UserProfileValues::default()
+            );
+        }
+
+        // Broken profile doesn't fail the tick
+        m.write_i32(USER_PROFILE + 0x7, 0x10090)
+            .write_i32(0x10090 + 0x4, 0x90000)
+            .write_i32(0x90000 + 0x30, 0x7FFF_0000);
+        process_reading_loop(&m, &mut state).unwrap();
+        {
+            let values = state.values.lock().unwrap();
+            assert_eq!(values.user_profile, UserProfileValues::default());
+            assert_eq!(values.beatmap.title, "Another Me");
+        }
+
+        assert!(matches!(
+            state.ivalues.skipped.errors.as_slice(),
+            [SkippedSection {
+                section: Section::UserProfile,
+                repeated: false,
+                ..
+            }]
+        ));
+
+        // Still broken, it's not reported again until profile is read
+        state.ivalues.skipped.errors.clear();
+        process_reading_loop(&m, &mut state).unwrap();
+        assert!(matches!(
+            state.ivalues.skipped.errors.as_slice(),
+            [SkippedSection {
+                section: Section::UserProfile,
+                repeated: true,
+                ..
+            }]
+        ));
+
+        state.ivalues.skipped.errors.clear();
+        m.write_i32(0x90000 + 0x30, 0x91000);
+        process_reading_loop(&m, &mut state).unwrap();
+        assert!(state.ivalues.skipped.errors.is_empty());
+        assert_eq!(state.values.lock().unwrap().user_profile.name, "Vaxei");
+
+        // Signature is optional
+        let mut m = memory_image();
+        m.remove_signature("FF 15 ?? ?? ?? ?? A1 ?? ?? ?? ?? 8B 48 54 33 D2");
+
+        let mut no_profile = self::state(&m);
+        assert_eq!(no_profile.addresses.user_profile, None);
+
+        process_reading_loop(&m, &mut no_profile).unwrap();
+        {
+            let values = no_profile.values.lock().unwrap();
+            assert_eq!(values.user_profile, UserProfileValues::default());
+            assert_eq!(values.settings.dim_level, 80);
+        }
+    }
+
+    #[test]
+    fn test_settings_failure_streak() {
+        let mut m = memory_image();
+        let mut state = state(&m);
+
+        let mut tick = |m: &FakeMemory| {
+            state.ivalues.settings_updated = None;
+            process_reading_loop(m, &mut state).unwrap();
+
+            state
+                .ivalues
+                .skipped
+                .errors
+                .drain(..)
+                .map(|v| (v.section, v.repeated))
+                .collect::<Vec<_>>()
+        };
+
+        // Config can't be read, only the first failure is new
+        m.write_i32(0x10080, 0x7FFF_0000);
+        assert_eq!(tick(&m), [(Section::Settings, false)]);
+        assert_eq!(tick(&m), [(Section::Settings, true)]);
+
+        // Streak ends after settings are read
+        m.write_i32(0x10080, 0x80000);
+        assert_eq!(tick(&m), []);
+
+        m.write_i32(0x10080, 0x7FFF_0000);
+        assert_eq!(tick(&m), [(Section::Settings, false)]);
+    }
+
+    #[test]
+    fn test_process_settings() {
+        let mut m = memory_image();
+        let mut settings = SettingsValues::default();
+
+        // Garbage count and unreadable first entry
+        m.write_i32(0x81000 + 0x1C, i32::MAX)
+            .write_i32(0x82000 + 0x8, 0x7FFF_0000);
+
+        process_settings(&m, &mut settings, SETTINGS).unwrap();
+        assert_eq!(settings.dim_level, 0);
+        assert!(settings.key_overlay);
+        assert_eq!(settings.skin, "lain's skin");
+
+        // Config isn't created yet
+        let mut settings = SettingsValues::default();
+        m.write_i32(0x10080, 0);
+
+        process_settings(&m, &mut settings, SETTINGS).unwrap();
+        assert_eq!(settings, SettingsValues::default());
+    }
+
+    #[test]
+    fn test_reading_loop_state_transitions() {
+        let mut m = memory_image();
+        let mut state = state(&m);
+
+        // SongSelect
+        process_reading_loop(&m, &mut state).unwrap();
+        {
+            let values = state.values.lock().unwrap();
+
+            assert_eq!(values.state, GameState::SongSelect);
+            assert_eq!(values.beatmap.title, "Another Me");
+            assert_eq!(values.beatmap.map_id, 2643167);
+            assert_eq!(values.beatmap.ar, 9.6);
+            assert_eq!(values.beatmap.beatmap_status, BeatmapStatus::Ranked);
+            assert_eq!(values.skin, "lain's skin");
+            assert_eq!(values.settings.dim_level, 80);
+            assert!(values.settings.key_overlay);
+            assert_eq!(values.settings.skin, "lain's skin");
+            assert!(!values.settings.fullscreen);
+            assert_eq!(values.user_profile.name, "Vaxei");
+            assert_eq!(values.user_profile.user_id, 4787150);
+            assert_eq!(values.user_profile.ranked_score, 40_000_000_000);
+            assert_eq!(values.user_profile.accuracy, 98.7);
+            assert_eq!(values.user_profile.rank, 3);
+            assert_eq!(values.user_profile.pp, 21000);
+            assert_eq!(values.user_profile.country_code, 225);
+            assert_eq!(values.plays, 3);
+            assert_eq!(values.precise_audio_time, 1234);
+            assert!(values.chat_enabled);
+        }
+
+        // Playing
+        // Gameplay values are reset on the tick when `Playing` state
+        // is entered, so they are available starting from the next one
+        m.write_u32(STATUS_VALUE, 2).write_i32(PLAYTIME_VALUE, 1000);
+        process_reading_loop(&m, &mut state).unwrap();
+        assert_eq!(state.values.lock().unwrap().state, GameState::Playing);
+
+        m.write_i32(PLAYTIME_VALUE, 1300);
+        process_reading_loop(&m, &mut state).unwrap();
+        {
+            let values = state.values.lock().unwrap();
+
+            assert_eq!(values.state, GameState::Playing);
+            assert_eq!(values.gameplay.username, "Vaxei");
+            assert_eq!(values.gameplay.hit_300, 10);
+            assert_eq!(values.gameplay.hit_100, 2);
+            assert_eq!(values.gameplay.hit_miss, 1);
+            assert_eq!(values.gameplay.passed_objects, 13);
+            assert_eq!(values.gameplay.combo, 5);
+            assert_eq!(values.gameplay.max_combo, 8);
+            assert_eq!(values.gameplay.mods, 72);
+            assert_eq!(values.mods_str, vec!["HD", "DT"]);
+            assert_eq!(values.gameplay.hit_errors, vec![2, -3, 10, -7]);
+            assert_eq!(values.gameplay.hit_errors_new.index, 0);
+            assert_eq!(values.gameplay.hit_errors_new.values, vec![2, -3, 10, -7]);
+            assert_eq!(values.gameplay.current_hp, 200.0);
+
+            assert_eq!(values.leaderboard.len(), 2);
+            assert_eq!(values.leaderboard[0].name, "mrekk");
+            assert_eq!(values.leaderboard[0].mods, 8);
+            assert_eq!(values.leaderboard[0].max_combo, 1811);
+            assert_eq!(values.leaderboard[0].hit_300, 1115);
+            assert!(!values.leaderboard[0].is_ourself);
+            assert_eq!(values.leaderboard[1].score, 500_000);
+            assert_eq!(values.leaderboard[1].combo, 5);
+            assert_eq!(values.leaderboard[1].mods, 72);
+            assert_eq!(values.leaderboard[1].position, 2);
+            assert!(values.leaderboard[1].is_passing);
+            assert!(values.leaderboard[1].is_ourself);
+            assert!(values.gameplay.unstable_rate > 0.0);
+        }
+
+        // Only new hit errors
+        m.write_i32(PLAYTIME_VALUE, 1600)
+            .write_i32_list(0x63000, 0x64000, &[2, -3, 10, -7, 5, 1]);
+        process_reading_loop(&m, &mut state).unwrap();
+        {
+            let values = state.values.lock().unwrap();
+
+            assert_eq!(values.gameplay.hit_errors, vec![2, -3, 10, -7, 5, 1]);
+            assert_eq!(values.gameplay.hit_errors_new.index, 4);
+            assert_eq!(values.gameplay.hit_errors_new.values, vec![5, 1]);
+        }
+
+        // Broken leaderboard slot doesn't affect other values
+        m.write_i32(PLAYTIME_VALUE, 1700)
+            .write_i32(0x72000 + 0x8, 0x7F0000)
+            .write_i16(SCORE + 0x94, 6);
+        process_reading_loop(&m, &mut state).unwrap();
+        {
+            let values = state.values.lock().unwrap();
+
+            assert!(values.leaderboard.is_empty());
+            assert_eq!(values.gameplay.combo, 6);
+            assert!(matches!(
+                state.ivalues.skipped.errors.as_slice(),
+                [SkippedSection {
+                    section: Section::Leaderboard,
+                    ..
+                }]
+            ));
+        }
+
+        // Garbage leaderboard size is not read
+        for size in [i32::MAX, -1] {
+            m.write_i32(0x71800 + 0xC, size);
+            let mut entries = Vec::new();
+            assert!(process_leaderboard(&m, &mut entries, RULESET).is_err());
+        }
+
+        // ResultScreen
+        m.write_u32(STATUS_VALUE, 7);
+        process_reading_loop(&m, &mut state).unwrap();
+        {
+            let values = state.values.lock().unwrap();
+
+            assert_eq!(values.state, GameState::ResultScreen);
+            assert_eq!(values.result_screen.score, 500000);
+            assert_eq!(values.result_screen.mods, 16);
+            assert_eq!(values.result_screen.hit_300, 90);
+            assert_eq!(values.result_screen.hit_miss, 2);
+
+            // Gameplay is reset after leaving `Playing` state
+            assert_eq!(values.gameplay.hit_300, 0);
+            assert_eq!(values.gameplay.combo, 0);
+        }
+
+        // Back to SongSelect
+        m.write_u32(STATUS_VALUE, 5);
+        process_reading_loop(&m, &mut state).unwrap();
+        assert_eq!(state.values.lock().unwrap().state, GameState::SongSelect);
+    }
+
+    #[test]
+    fn test_process_precise() {
+        let mut m = memory_image();
+        let state = state(&m);
+        let mut precise = PreciseValues::default();
+
+        // Only audio time outside of gameplay
+        process_precise(&m, &state, &mut precise).unwrap();
+        assert_eq!(precise.precise_audio_time, 1234);
+        assert_eq!(precise.hit_errors_count, 0);
+
+        let hit_errors: Vec<i32> = (0..40).collect();
+        m.write_u32(STATUS_VALUE, 2)
+            .write_i32_list(0x63000, 0x64000, &hit_errors);
+
+        process_precise(&m, &state, &mut precise).unwrap();
+        assert_eq!(precise.hit_errors_count, 40);
+        assert_eq!(precise.hit_errors, hit_errors[40 - PRECISE_HIT_ERRORS..]);
+
+        // Nothing changes in shared values
+        assert_eq!(state.values.lock().unwrap().precise_audio_time, 0);
+    }
+
+    #[test]
+    fn test_reading_loop_events() {
+        let mut m = memory_image();
+        let mut state = state(&m);
+
+        process_reading_loop(&m, &mut state).unwrap();
+        let events: Vec<_> = state.ivalues.events.drain(..).collect();
+
+        // No `beatmap_changed`, because there is no .osu file to parse
+        assert!(matches!(
+            events.as_slice(),
+            [GameEvent::StateChanged {
+                from: GameState::Unknown,
+                to: GameState::SongSelect
+            }]
+        ));
+
+        // Nothing changed
+        process_reading_loop(&m, &mut state).unwrap();
+        assert!(state.ivalues.events.is_empty());
+
+        m.write_u32(STATUS_VALUE, 2)
+            .write_i32(PLAYTIME_VALUE, 1000)
+            .write_u32(0x10000, 72);
+        process_reading_loop(&m, &mut state).unwrap();
+        m.write_i32(PLAYTIME_VALUE, 1300);
+        process_reading_loop(&m, &mut state).unwrap();
+        let events: Vec<_> = state.ivalues.events.drain(..).collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                GameEvent::StateChanged {
+                    to: GameState::Playing,
+                    ..
+                },
+                GameEvent::PlayStarted {
+                    map_id: 2643167,
+                    mods: 72,
+                    mode: 0,
+                },
+            ]
+        ));
+
+        // Retry
+        m.write_i32(PLAYTIME_VALUE, 100);
+        process_reading_loop(&m, &mut state).unwrap();
+        let events: Vec<_> = state.ivalues.events.drain(..).collect();
+        assert!(matches!(
+            events.as_slice(),
+            [GameEvent::PlayRetried {
+                map_id: 2643167,
+                ..
+            }]
+        ));
+
+        m.write_i32(PLAYTIME_VALUE, 1300);
+        process_reading_loop(&m, &mut state).unwrap();
+        state.ivalues.events.clear();
+
+        m.write_u32(STATUS_VALUE, 7);
+        process_reading_loop(&m, &mut state).unwrap();
+        let events: Vec<_> = state.ivalues.events.drain(..).collect();
+        match events.as_slice() {
+            [
+                GameEvent::PlayCompleted(play),
+                GameEvent::StateChanged {
+                    from: GameState::Playing,
+                    to: GameState::ResultScreen,
+                },
+            ] => {
+                assert_eq!(play.map_id, 2643167);
+                assert_eq!(play.mods, 72);
+                assert_eq!(play.max_combo, 8);
+                assert_eq!(play.hit_miss, 1);
+                assert_eq!(play.playtime, 1300);
+            }
+            events => panic!("unexpected events: {:?}", events),
+        }
+    }
+
+    #[test]
+    fn test_reading_loop_events_failed_tick() {
+        let mut m = memory_image();
+        let mut state = state(&m);
+
+        m.write_u32(STATUS_VALUE, 2).write_i32(PLAYTIME_VALUE, 1000);
+        process_reading_loop(&m, &mut state).unwrap();
+        m.write_i32(PLAYTIME_VALUE, 1300);
+        process_reading_loop(&m, &mut state).unwrap();
+        state.ivalues.events.clear();
+
+        // Result screen isn't ready on the first tick, events
+        // are kept by the reader until a tick is finished
+        m.write_u32(STATUS_VALUE, 7)
+            .write_i32(RULESET + 0x38, 0x7FFF_0000);
+        assert!(process_reading_loop(&m, &mut state).is_err());
+
+        m.write_i32(RULESET + 0x38, RESULT_SCREEN);
+        process_reading_loop(&m, &mut state).unwrap();
+        process_reading_loop(&m, &mut state).unwrap();
+
+        let events: Vec<_> = state.ivalues.events.drain(..).collect();
+        match events.as_slice() {
+            [
+                GameEvent::PlayCompleted(play),
+                GameEvent::StateChanged {
+                    from: GameState::Playing,
+                    to: GameState::ResultScreen,
+                },
+            ] => {
+                assert_eq!(play.map_id, 2643167);
+                assert_eq!(play.max_combo, 8);
+            }
+            events => panic!("unexpected events: {:?}", events),
+        }
+
+        assert_eq!(state.values.lock().unwrap().result_screen.username, "Vaxei");
+    }
+
+    #[test]
+    fn test_reading_loop_missing_memory() {
+        let m = memory_image();
+        let mut state = state(&m);
+
+        let empty = FakeMemory::default();
+        assert!(process_reading_loop(&empty, &mut state).is_err());
+    }
+}
@@ -0,0 +1,220 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    network::{
+        broadcast::{FrameQueue, Snapshot},
+        patch::{apply_merge_patch, merge_patch},
+    },
+    structs::{OutputValues, RawValues, WsKind},
+};
+
+/// Single reading loop tick stored inside session file.
+/// Session file is a sequence of ticks, one JSON object per line
+#[derive(Serialize, Deserialize)]
+pub struct SessionTick {
+    /// Milliseconds since recording started
+    pub time: u64,
+
+    /// Values read directly from memory
+    pub raw: RawValues,
+
+    /// `OutputValues` as JSON Merge Patch against the previous
+    /// tick, so the first tick has all of them. `None` when
+    /// nothing changed, both endpoints are rebuilt from them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<Value>,
+}
+
+pub struct Recorder {
+    writer: BufWriter<File>,
+    started: Instant,
+
+    /// Values of the previous tick
+    last: Value,
+}
+
+impl Recorder {
+    pub fn new(path: &Path) -> Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+            started: Instant::now(),
+            last: Value::Null,
+        })
+    }
+
+    pub fn record(&mut self, raw: &RawValues, snapshot: &Snapshot) -> Result<()> {
+        let _span = tracy_client::span!("record tick");
+
+        let Some(values) = snapshot.value(WsKind::Rosu) else {
+            return Ok(());
+        };
+
+        let tick = SessionTick {
+            time: self.started.elapsed().as_millis() as u64,
+            raw: *raw,
+            values: merge_patch(&self.last, values),
+        };
+
+        self.last.clone_from(values);
+
+        serde_json::to_writer(&mut self.writer, &tick)?;
+        self.writer.write_all(b"\n")?;
+
+        // Flushing every tick, so session is usable
+        // even if reader was killed
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Feeds recorded ticks to the clients keeping original timing
+///
+/// * `speed` - playback speed multiplier
+/// * `seek` - ticks before this point are skipped
+pub fn replay(
+    path: &Path,
+    speed: f64,
+    seek: Duration,
+    snapshots: &FrameQueue<Arc<Snapshot>>,
+) -> Result<()> {
+    let reader = BufReader::new(File::open(path)?);
+    let seek = seek.as_millis() as u64;
+
+    let started = Instant::now();
+
+    let mut current = Value::Null;
+
+    // Hit errors are not serialized, they
+    // are rebuilt from `hit_errors_new`
+    let mut hit_errors = Vec::new();
+    let mut hit_errors_generation = 0u32;
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let tick: SessionTick = serde_json::from_str(&line)?;
+
+        // Skipped ticks are still applied, the
+        // next ones are patches on top of them
+        if let Some(ref patch) = tick.values {
+            apply_merge_patch(&mut current, patch);
+        }
+
+        let mut values = OutputValues::deserialize(current.clone())?;
+        let new = &values.gameplay.hit_errors_new;
+
+        if new.index == 0 {
+            hit_errors_generation = hit_errors_generation.wrapping_add(1);
+        }
+
+        if new.index <= hit_errors.len() {
+            hit_errors.truncate(new.index);
+            hit_errors.extend_from_slice(&new.values);
+        }
+
+        if tick.time < seek {
+            continue;
+        }
+
+        let due = Duration::from_secs_f64((tick.time - seek) as f64 / 1000.0 / speed);
+
+        if let Some(wait) = due.checked_sub(started.elapsed()) {
+            thread::sleep(wait);
+        }
+
+        values.gameplay.hit_errors.clone_from(&hit_errors);
+        values.gameplay.hit_errors_generation = hit_errors_generation;
+
+        snapshots.push(Arc::new(Snapshot::new(&values)));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+
+    use super::*;
+    use crate::{network::broadcast::HitErrorsCursor, structs::GameState};
+
+    #[test]
+    fn test_record_and_replay() {
+        let path = std::env::temp_dir().join(format!("rosu-session-{}.jsonl", std::process::id()));
+
+        let mut values = OutputValues {
+            state: GameState::SongSelect,
+            mods_str: vec!["HD", "DT"],
+            ..Default::default()
+        };
+        values.beatmap.title = "Another Me".to_owned();
+
+        let mut recorded = vec![Snapshot::new(&values)];
+
+        values.state = GameState::Playing;
+        values.gameplay.grade = "SH";
+        values.gameplay.hit_errors = vec![2, -3];
+        values.gameplay.hit_errors_new.values = vec![2, -3];
+        recorded.push(Snapshot::new(&values));
+
+        values.gameplay.hit_errors.push(10);
+        values.gameplay.hit_errors_new.index = 2;
+        values.gameplay.hit_errors_new.values = vec![10];
+        recorded.push(Snapshot::new(&values));
+        recorded.push(Snapshot::new(&values));
+
+        let mut recorder = Recorder::new(&path).unwrap();
+        for snapshot in &recorded {
+            recorder.record(&RawValues::default(), snapshot).unwrap();
+        }
+
+        // Unchanged tick has no values at all
+        let session = fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = session.lines().collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[2].len() < lines[0].len());
+        assert!(!lines[3].contains("values"));
+
+        let snapshots = FrameQueue::new(8);
+        replay(&path, 1000.0, Duration::ZERO, &snapshots).unwrap();
+
+        let rx = snapshots.receiver();
+        let mut replayed = Vec::new();
+
+        while let Ok(snapshot) = rx.try_recv() {
+            replayed.push(snapshot);
+        }
+
+        assert_eq!(replayed.len(), recorded.len());
+
+        for (replayed, expected) in replayed.iter().zip(&recorded) {
+            for kind in [WsKind::Rosu, WsKind::Gosu] {
+                assert_eq!(replayed.json(kind), expected.json(kind));
+            }
+        }
+
+        // Hit errors are rebuilt for clients connecting mid-play
+        assert_eq!(
+            HitErrorsCursor::default().resync(replayed.last().unwrap()),
+            HitErrorsCursor::default().resync(recorded.last().unwrap()),
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// Parsed `skin.ini`, only the parts overlays care about
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct SkinIni {
+    pub name: String,
+    pub author: String,
+    pub version: String,
+
+    /// `Combo1`..`Combo8` in order, RGB
+    pub combo_colours: Vec<[u8; 3]>,
+
+    /// The rest of `[Colours]` section, e.g. `SliderBorder`.
+    /// RGB or RGBA
+    pub colours: BTreeMap<String, Vec<u8>>,
+
+    /// `[Fonts]` section as is, e.g. `ScorePrefix`
+    pub fonts: BTreeMap<String, String>,
+}
+
+impl SkinIni {
+    pub fn parse(text: &str) -> Self {
+        let mut skin = Self::default();
+        let mut combo_colours = BTreeMap::new();
+        let mut section = "";
+
+        for line in text.trim_start_matches('\u{feff}').lines() {
+            let line = line.trim();
+
+            if line.starts_with("//") {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+                section = name;
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+
+            let (key, value) = (key.trim(), value.trim());
+
+            match section {
+                "General" => match key {
+                    "Name" => skin.name = value.to_owned(),
+                    "Author" => skin.author = value.to_owned(),
+                    "Version" => skin.version = value.to_owned(),
+                    _ => (),
+                },
+                "Colours" => {
+                    let Some(colour) = parse_colour(value) else {
+                        continue;
+                    };
+
+                    match key.strip_prefix("Combo").map(str::parse::<u8>) {
+                        Some(Ok(i)) => {
+                            combo_colours.insert(i, [colour[0], colour[1], colour[2]]);
+                        }
+                        _ => {
+                            skin.colours.insert(key.to_owned(), colour);
+                        }
+                    }
+                }
+                "Fonts" => {
+                    skin.fonts.insert(key.to_owned(), value.to_owned());
+                }
+                _ => (),
+            }
+        }
+
+        skin.combo_colours = combo_colours.into_values().collect();
+
+        skin
+    }
+}
+
+/// Parses `r,g,b` or `r,g,b,a`
+fn parse_colour(value: &str) -> Option<Vec<u8>> {
+    let colour: Vec<u8> = value
+        .split(',')
+        .map(|v| v.trim().parse().ok())
+        .collect::<Option<_>>()?;
+
+    matches!(colour.len(), 3 | 4).then_some(colour)
+}
+
+/// osu! uses `@2x` version of the element when regular one
+/// is missing and vice versa, returns the other file name
+pub fn fallback_name(file: &str) -> Option<String> {
+    let (stem, extension) = file.rsplit_once('.')?;
+
+    match stem.strip_suffix("@2x") {
+        Some(stem) => Some(format!("{stem}.{extension}")),
+        None => Some(format!("{stem}@2x.{extension}")),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_skin_ini() {
+        let skin = SkinIni::parse(
+            "\u{feff}// comment\n\
+             [General]\n\
+             Name: lain's skin\n\
+             Author: lain\n\
+             Version: 2.7\n\
+             \n\
+             [Colours]\n\
+             Combo2: 0, 128, 255\n\
+             Combo1: 255,0,0\n\
+             SliderBorder: 255,255,255,200\n\
+             SliderTrackOverride: broken\n\
+             \n\
+             [Fonts]\n\
+             ScorePrefix: Fonts/score\n\
+             ScoreOverlap: 3\n",
+        );
+
+        assert_eq!(skin.name, "lain's skin");
+        assert_eq!(skin.author, "lain");
+        assert_eq!(skin.version, "2.7");
+        assert_eq!(skin.combo_colours, vec![[255, 0, 0], [0, 128, 255]]);
+        assert_eq!(skin.colours["SliderBorder"], vec![255, 255, 255, 200]);
+        assert!(!skin.colours.contains_key("SliderTrackOverride"));
+        assert_eq!(skin.fonts["ScorePrefix"], "Fonts/score");
+        assert_eq!(skin.fonts["ScoreOverlap"], "3");
+    }
+
+    #[test]
+    fn test_fallback_name() {
+        assert_eq!(
+            fallback_name("hitcircle.png").as_deref(),
+            Some("hitcircle@2x.png")
+        );
+        assert_eq!(
+            fallback_name("score-0@2x.png").as_deref(),
+            Some("score-0.png")
+        );
+        assert_eq!(fallback_name("readme"), None);
+    }
+}
@@ -3,14 +3,10 @@ use std::{
     path::PathBuf,
     str::FromStr,
     sync::{Arc, Mutex},
+    time::Instant,
 };
 
-use async_tungstenite::WebSocketStream;
-use hyper::upgrade::Upgraded;
-use rosu_mem::{
-    process::{Process, ProcessTraits},
-    signature::Signature,
-};
+use rosu_mem::signature::Signature;
 
 use rosu_pp::{
     any::{PerformanceAttributes, ScoreState},
@@ -18,12 +14,14 @@ use rosu_pp::{
     Beatmap, Difficulty, GradualPerformance, Performance,
 };
 
-use eyre::Result;
-use serde::Serialize;
-use serde_repr::Serialize_repr;
+use eyre::{Report, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
 
 use crate::{
-    network::smol_hyper::SmolIo,
+    events::GameEvent,
+    memory::MemoryReader,
+    network::broadcast::{Frame, FrameQueue},
     utils::{effect_point_at, timing_point_at},
 };
 
@@ -32,11 +30,20 @@ use crate::{
 pub enum WsKind {
     Gosu,
     Rosu,
+    Events,
+    Precise,
 }
 
 pub struct WsClient {
     pub kind: WsKind,
-    pub client: WebSocketStream<SmolIo<Upgraded>>,
+
+    /// Whether client receives game events
+    pub events: bool,
+
+    /// Connected through `/sse` instead of websocket
+    pub sse: bool,
+
+    pub queue: FrameQueue<Frame>,
 }
 
 pub type Arm<T> = Arc<Mutex<T>>;
@@ -113,7 +120,52 @@ const MODS: [(u32, &str); 31] = [
     (1 << 30, "MR"),
 ];
 
-#[derive(Serialize_repr, Debug, Default, PartialEq, Eq, Clone, Copy)]
+/// Every grade [`GameplayValues::get_current_grade`] returns
+const GRADES: [&str; 9] = ["", "SSH", "SH", "SS", "S", "A", "B", "C", "D"];
+
+/// Grade and mods are always one of the known
+/// strings, so they are deserialized without leaking
+fn known_str<E: serde::de::Error>(
+    value: &str,
+    known: impl IntoIterator<Item = &'static str>,
+) -> Result<&'static str, E> {
+    known
+        .into_iter()
+        .find(|v| *v == value)
+        .ok_or_else(|| E::custom(format!("unknown value: {value}")))
+}
+
+fn deserialize_grade<'de, D: Deserializer<'de>>(d: D) -> Result<&'static str, D::Error> {
+    known_str(&String::deserialize(d)?, GRADES)
+}
+
+fn deserialize_mods<'de, D: Deserializer<'de>>(d: D) -> Result<Vec<&'static str>, D::Error> {
+    Vec::<String>::deserialize(d)?
+        .iter()
+        .map(|v| known_str(v, MODS.iter().map(|(_, name)| *name)))
+        .collect()
+}
+
+/// Acronyms of `mods`, e.g. `["HD", "NC"]`
+pub fn readable_mods(mods: u32) -> Vec<&'static str> {
+    let mut mods_str: Vec<&'static str> = MODS
+        .iter()
+        .filter(|(idx, _)| mods & idx > 0)
+        .map(|(_, name)| *name)
+        .collect();
+
+    if mods_str.contains(&"NC") {
+        mods_str.retain(|x| x != &"DT");
+    }
+
+    if mods_str.contains(&"PF") {
+        mods_str.retain(|x| x != &"SD");
+    }
+
+    mods_str
+}
+
+#[derive(Serialize_repr, Deserialize_repr, Debug, Default, PartialEq, Eq, Clone, Copy)]
 #[repr(u32)]
 pub enum GameState {
     PreSongSelect = 0,
@@ -145,7 +197,7 @@ impl From<u32> for GameState {
     }
 }
 
-#[derive(Serialize_repr, Debug, Default, PartialEq, Eq, Copy, Clone)]
+#[derive(Serialize_repr, Deserialize_repr, Debug, Default, PartialEq, Eq, Copy, Clone)]
 #[repr(i16)]
 pub enum BeatmapStatus {
     #[default]
@@ -184,47 +236,104 @@ pub struct StaticAddresses {
     pub skin: i32,
     pub chat_checker: i32,
     pub audio_time_base: i32,
+
+    // Optional, values that depend on these are
+    // not read when signature isn't found
+    pub settings: Option<i32>,
+    pub user_profile: Option<i32>,
+}
+
+/// Whether static signature was found, reported by `/status`
+#[derive(Debug, Clone, Serialize)]
+pub struct SignatureStatus {
+    pub name: &'static str,
+    pub address: Option<i32>,
 }
 
 impl StaticAddresses {
-    pub fn new(p: &Process) -> Result<Self> {
+    pub fn new(p: &impl MemoryReader) -> Result<Self> {
+        Self::resolve(p).0
+    }
+
+    /// Same as [`StaticAddresses::new`] but doesn't stop on
+    /// the first missing signature, reports every one of them.
+    /// Missing optional signatures are not an error
+    pub fn resolve(p: &impl MemoryReader) -> (Result<Self>, Vec<SignatureStatus>) {
         let _span = tracy_client::span!("static addresses");
 
-        let base_sign = Signature::from_str("F8 01 74 04 83 65")?;
-        let status_sign = Signature::from_str("48 83 F8 04 73 1E")?;
-        let menu_mods_sign =
-            Signature::from_str("C8 FF ?? ?? ?? ?? ?? 81 0D ?? ?? ?? ?? 00 08 00 00")?;
+        let mut statuses = Vec::new();
+        let mut error = None;
 
-        let rulesets_sign = Signature::from_str("7D 15 A1 ?? ?? ?? ?? 85 C0")?;
+        let mut read = |name, sign: &str| {
+            let res = Signature::from_str(sign)
+                .map_err(Report::from)
+                .and_then(|sign| p.read_signature(&sign).map_err(Report::from));
 
-        let playtime_sign = Signature::from_str("5E 5F 5D C3 A1 ?? ?? ?? ?? 89 ?? 04")?;
+            statuses.push(SignatureStatus {
+                name,
+                address: res.as_ref().ok().copied(),
+            });
 
-        let skin_sign = Signature::from_str("74 2C 85 FF 75 28 A1 ?? ?? ?? ?? 8D 15")?;
+            res
+        };
 
-        let chat_checker = Signature::from_str("0A D7 23 3C 00 00 ?? 01")?;
+        let mut required = |res: Result<i32>| {
+            res.unwrap_or_else(|e| {
+                error.get_or_insert(e);
+                0
+            })
+        };
 
-        let audio_time_base = Signature::from_str("DB 5C 24 34 8B 44 24 34")?;
+        let addresses = Self {
+            base: required(read("base", "F8 01 74 04 83 65")),
+            status: required(read("status", "48 83 F8 04 73 1E")),
+            menu_mods: required(read(
+                "menu_mods",
+                "C8 FF ?? ?? ?? ?? ?? 81 0D ?? ?? ?? ?? 00 08 00 00",
+            )),
+            rulesets: required(read("rulesets", "7D 15 A1 ?? ?? ?? ?? 85 C0")),
+            playtime: required(read("playtime", "5E 5F 5D C3 A1 ?? ?? ?? ?? 89 ?? 04")),
+            skin: required(read("skin", "74 2C 85 FF 75 28 A1 ?? ?? ?? ?? 8D 15")),
+            chat_checker: required(read("chat_checker", "0A D7 23 3C 00 00 ?? 01")),
+            audio_time_base: required(read("audio_time_base", "DB 5C 24 34 8B 44 24 34")),
+            settings: read(
+                "settings",
+                "7E 07 8D 65 F8 5E 5F 5D C3 E8 ?? ?? ?? ?? 8B 0D ?? ?? ?? ?? 33 D2 39 09 FF 15",
+            )
+            .ok(),
+            user_profile: read(
+                "user_profile",
+                "FF 15 ?? ?? ?? ?? A1 ?? ?? ?? ?? 8B 48 54 33 D2",
+            )
+            .ok(),
+        };
 
-        Ok(Self {
-            base: p.read_signature(&base_sign)?,
-            status: p.read_signature(&status_sign)?,
-            menu_mods: p.read_signature(&menu_mods_sign)?,
-            rulesets: p.read_signature(&rulesets_sign)?,
-            playtime: p.read_signature(&playtime_sign)?,
-            skin: p.read_signature(&skin_sign)?,
-            chat_checker: p.read_signature(&chat_checker)?,
-            audio_time_base: p.read_signature(&audio_time_base)?,
-        })
+        match error {
+            Some(e) => (Err(e), statuses),
+            None => (Ok(addresses), statuses),
+        }
     }
 }
 
 pub struct State {
     pub addresses: StaticAddresses,
-    pub clients: Clients,
     pub values: Arm<OutputValues>,
     pub ivalues: InnerValues,
 }
 
+/// Values as they were read from memory,
+/// before any processing. Used for session recording
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize)]
+pub struct RawValues {
+    pub status: u32,
+    pub menu_mods: u32,
+    pub menu_mode: i32,
+    pub playtime: i32,
+    pub precise_audio_time: i32,
+    pub beatmap_addr: i32,
+    pub ruleset_addr: i32,
+}
+
 // Inner values that used only inside
 // reading loop and shouldn't be
 // shared between any threads
@@ -234,6 +343,27 @@ pub struct InnerValues {
 
     /// Used for recalculations on fc_pp
     pub current_beatmap_perf: Option<PerformanceAttributes>,
+
+    /// Raw values of the last reading loop tick
+    pub raw: RawValues,
+
+    /// Events detected since the last finished reading loop tick
+    pub events: Vec<GameEvent>,
+
+    /// State that was reported in the last `StateChanged` event
+    pub last_state: GameState,
+
+    /// Beatmaps that failed to parse since start
+    pub beatmap_parse_failures: u64,
+
+    /// Address of the hit errors list they were read from
+    pub hit_errors_addr: i32,
+
+    /// Parts of the tick that were skipped, see [`Section`]
+    pub skipped: SkippedSections,
+
+    /// When settings were read the last time
+    pub settings_updated: Option<Instant>,
 }
 
 impl InnerValues {
@@ -243,7 +373,99 @@ impl InnerValues {
     }
 }
 
-#[derive(Debug, Default, Serialize)]
+/// Parts of the reading loop tick that are skipped
+/// on errors instead of failing the whole tick
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Beatmap,
+    Performance,
+    Gameplay,
+    Leaderboard,
+    Settings,
+    UserProfile,
+}
+
+impl Section {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Beatmap => "beatmap",
+            Self::Performance => "performance",
+            Self::Gameplay => "gameplay",
+            Self::Leaderboard => "leaderboard",
+            Self::Settings => "settings",
+            Self::UserProfile => "user_profile",
+        }
+    }
+}
+
+pub struct SkippedSection {
+    pub section: Section,
+    pub error: Report,
+
+    /// Whether section failed on its previous attempt as well
+    pub repeated: bool,
+}
+
+/// Tracks failure streaks of [`Section`]s, so a section
+/// that can't be read is reported once instead of every tick
+#[derive(Default)]
+pub struct SkippedSections {
+    /// Sections that failed on their last attempt
+    failing: Vec<Section>,
+
+    /// Errors since they were taken the last time
+    pub errors: Vec<SkippedSection>,
+}
+
+impl SkippedSections {
+    /// Records result of the section, returns whether it was read
+    pub fn report(&mut self, section: Section, res: Result<()>) -> bool {
+        let repeated = self.failing.contains(&section);
+
+        match res {
+            Ok(()) => {
+                self.failing.retain(|v| *v != section);
+                true
+            }
+            Err(error) => {
+                if !repeated {
+                    self.failing.push(section);
+                }
+
+                self.errors.push(SkippedSection {
+                    section,
+                    error,
+                    repeated,
+                });
+
+                false
+            }
+        }
+    }
+}
+
+/// Amount of the last hit errors in [`PreciseValues`]
+pub const PRECISE_HIT_ERRORS: usize = 32;
+
+/// Values that are read between regular ticks at
+/// a higher rate, without beatmap and pp processing
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PreciseValues {
+    pub precise_audio_time: i32,
+
+    /// Only updated while `Playing`
+    pub keyoverlay: KeyOverlayValues,
+
+    /// Last [`PRECISE_HIT_ERRORS`] hit errors
+    pub hit_errors: Vec<i32>,
+
+    /// Total amount of hit errors, first of `hit_errors`
+    /// has index `hit_errors_count - hit_errors.len()`
+    pub hit_errors_count: usize,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KeyOverlayValues {
     pub k1_pressed: bool,
     pub k1_count: u32,
@@ -268,7 +490,8 @@ impl KeyOverlayValues {
     }
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ResultScreenValues {
     pub username: String,
     pub mods: u32,
@@ -296,7 +519,8 @@ impl ResultScreenValues {
     }
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BeatmapPathValues {
     /// Absolute beatmap file path
     /// Example: `/path/to/osu/Songs/124321 Artist - Title/my_map.osu`
@@ -323,7 +547,8 @@ pub struct BeatmapPathValues {
     pub audio_file: String,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BeatmapValues {
     pub artist: String,
     pub title: String,
@@ -367,11 +592,29 @@ pub struct BeatmapValues {
     pub paths: BeatmapPathValues,
 }
 
-#[derive(Debug, Default, Serialize)]
+/// Hit errors that were added during the last tick
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HitErrorsNew {
+    /// Index of the first new hit error among all hit errors
+    pub index: usize,
+
+    pub values: Vec<i32>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct GameplayValues {
     #[serde(skip)]
     pub hit_errors: Vec<i32>,
 
+    /// Changes every time `hit_errors` start over, lets server
+    /// tell apart hit errors of different plays
+    #[serde(skip)]
+    pub hit_errors_generation: u32,
+
+    pub hit_errors_new: HitErrorsNew,
+
     pub mods: u32,
 
     pub username: String,
@@ -391,7 +634,7 @@ pub struct GameplayValues {
 
     pub passed_objects: usize,
 
-    #[serde(default = "SS")]
+    #[serde(deserialize_with = "deserialize_grade")]
     pub grade: &'static str,
     pub current_hp: f64,
     pub current_hp_smooth: f64,
@@ -540,7 +783,88 @@ impl GameplayValues {
     }
 }
 
-#[derive(Debug, Default, Serialize)]
+/// osu! client settings, `osu!.<username>.cfg` as it's
+/// loaded by the game
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SettingsValues {
+    pub volume_universal: i32,
+    pub volume_music: i32,
+    pub volume_effect: i32,
+
+    /// Universal offset in ms
+    pub offset: i32,
+
+    /// Background dim in percents
+    pub dim_level: i32,
+    pub mania_speed: i32,
+    pub key_overlay: bool,
+    pub show_interface: bool,
+    pub ignore_beatmap_skins: bool,
+    pub ignore_beatmap_samples: bool,
+
+    /// Name of the skin folder
+    pub skin: String,
+    pub width: i32,
+    pub height: i32,
+    pub fullscreen: bool,
+}
+
+/// Logged in player as shown on the bancho user panel,
+/// everything is zero when nobody is logged in
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserProfileValues {
+    pub name: String,
+    pub user_id: i32,
+    pub accuracy: f64,
+    pub ranked_score: i64,
+    pub level: f32,
+    pub play_count: i32,
+    pub play_mode: i32,
+
+    /// Global rank
+    pub rank: i32,
+    pub pp: i16,
+
+    /// Raw bancho status, e.g. idle, afk or playing
+    pub bancho_status: u8,
+
+    /// Raw osu! country index
+    pub country_code: i32,
+}
+
+/// Player slot of the in-game leaderboard
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LeaderboardEntry {
+    pub name: String,
+    pub score: i32,
+
+    /// Only visible in multiplayer or for ourselves
+    pub combo: i16,
+    pub max_combo: i16,
+    pub mods: u32,
+    pub hit_300: i16,
+    pub hit_100: i16,
+    pub hit_50: i16,
+    pub hit_miss: i16,
+
+    /// `0` - solo, `1` or `2` - blue or red
+    pub team: i32,
+    pub position: i32,
+    pub is_passing: bool,
+
+    /// Whether slot belongs to the player who is playing
+    pub is_ourself: bool,
+}
+
+// Grade and mods are `&'static str`, so values can
+// only be deserialized from owned data like `Value`.
+// Missing fields are default, NaN floats are
+// serialized as `null` and patched out in sessions
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, bound(deserialize = "'de: 'static"))]
 pub struct OutputValues {
     /// Absolute path to the osu! folder
     /// Example: `/path/to/osu`
@@ -589,6 +913,9 @@ pub struct OutputValues {
     /// Current state of the game
     pub state: GameState,
 
+    /// Interval between updates in ms, depends on `state`
+    pub polling_interval: u64,
+
     /// Stars of current beatmap without any mods
     pub stars: f64,
 
@@ -609,6 +936,16 @@ pub struct OutputValues {
     /// Gameplay info
     pub gameplay: GameplayValues,
 
+    /// Leaderboard slots during gameplay in score order,
+    /// empty when leaderboard is not shown
+    pub leaderboard: Vec<LeaderboardEntry>,
+
+    /// Client settings, updated every few seconds
+    pub settings: SettingsValues,
+
+    /// Logged in player, available in every state
+    pub user_profile: UserProfileValues,
+
     /// Beatmap info
     pub beatmap: BeatmapValues,
 
@@ -651,6 +988,7 @@ pub struct OutputValues {
     /// `Playing` => using gameplay mods
     /// `SongSelect` => using menu_mods
     /// `ResultScreen` => using result_screen mods
+    #[serde(deserialize_with = "deserialize_mods")]
     pub mods_str: Vec<&'static str>,
 
     pub plays: i32,
@@ -702,6 +1040,10 @@ impl OutputValues {
         self.gameplay.current_hp_smooth = 0.0;
 
         self.gameplay.unstable_rate = 0.0;
+        self.gameplay.hit_errors.clear();
+        self.gameplay.hit_errors_new = HitErrorsNew::default();
+
+        self.leaderboard.clear();
 
         ivalues.reset();
     }
@@ -837,11 +1179,14 @@ impl OutputValues {
                         let res = grad.nth(score_state.clone(), self.gameplay.passed_objects);
 
                         if res.is_none() {
-                            println!("
-                                Failed to advance gradual pp forward: passed_objects: {}, grad_remaining_objects: {}",
-                                self.gameplay.passed_objects,
-                                grad.len()
-                            )
+                            ivalues.skipped.report(
+                                Section::Performance,
+                                Err(Report::msg(format!(
+                                    "Failed to advance gradual pp forward: passed_objects: {}, grad_remaining_objects: {}",
+                                    self.gameplay.passed_objects,
+                                    grad.len()
+                                ))),
+                            );
                         };
 
                         self.prev_passed_objects = self.gameplay.passed_objects;
@@ -863,13 +1208,17 @@ impl OutputValues {
                     Some(attributes) => {
                         self.current_pp = attributes.pp();
                         self.current_stars = attributes.stars();
+                        ivalues.skipped.report(Section::Performance, Ok(()));
                     }
                     None => {
-                        println!(
-                            "Failed to calculate current pp/sr, delta_sum: {}, delta_curr: {}",
-                            self.delta_sum,
-                            delta - 1
-                        )
+                        ivalues.skipped.report(
+                            Section::Performance,
+                            Err(Report::msg(format!(
+                                "Failed to calculate current pp/sr, delta_sum: {}, delta_curr: {}",
+                                self.delta_sum,
+                                delta - 1
+                            ))),
+                        );
                     }
                 }
             }
@@ -1026,21 +1375,7 @@ impl OutputValues {
             _ => self.menu_mods,
         };
 
-        self.mods_str.clear();
-
-        MODS.iter().for_each(|(idx, name)| {
-            if let Some(m) = (mods_values & idx > 0).then_some(*name) {
-                self.mods_str.push(m);
-            }
-        });
-
-        if self.mods_str.contains(&"NC") {
-            self.mods_str.retain(|x| x != &"DT");
-        }
-
-        if self.mods_str.contains(&"PF") {
-            self.mods_str.retain(|x| x != &"SD");
-        }
+        self.mods_str = readable_mods(mods_values);
     }
 
     /// Depends on `BeatmapValues` and `BeatmapPathValues`